@@ -8,6 +8,21 @@ use syn::{DeriveInput, parse_macro_input};
 struct Opts {
     #[darling(default)]
     status: Option<syn::Path>,
+    /// `#[respond(from = "field")]`: respond with the status of the error in `field`
+    #[darling(default)]
+    from: Option<syn::Ident>,
+}
+
+/// Support `#[respond("Unauthorized")]` as shorthand for `rocket::http::Status::Unauthorized`.
+fn shorthand_status(variant: &syn::Variant) -> Option<proc_macro2::TokenStream> {
+    let attr = variant
+        .attrs
+        .iter()
+        .find(|attr| attr.path().is_ident("respond"))?;
+    let name = attr.parse_args::<syn::LitStr>().ok()?;
+    let ident = syn::Ident::new(&name.value(), name.span());
+
+    Some(quote! { ::rocket::http::Status::#ident })
 }
 
 #[proc_macro_derive(ErrorResponder, attributes(respond))]
@@ -37,25 +52,34 @@ pub fn error_responder(input: TokenStream) -> TokenStream {
             syn::Fields::Named(_) => quote! { &Self::#v_ident { .. } },
         };
 
-        if let Ok(x) = Opts::from_variant(v) {
-            if let Some(y) = x.status {
-                let z = y.into_token_stream();
-                quote! { #pat => #z, }
-            } else {
-                quote! {}
-            }
+        let opts = Opts::from_variant(v).unwrap_or_default();
+
+        if let Some(field) = opts.from {
+            quote! { &Self::#v_ident { ref #field, .. } => #field.status(), }
+        } else if let Some(y) = opts.status {
+            let z = y.into_token_stream();
+            quote! { #pat => #z, }
+        } else if let Some(z) = shorthand_status(v) {
+            quote! { #pat => #z, }
         } else {
             quote! {}
         }
     });
 
     let output = quote! {
-        impl<'r, 'o: 'r> ::rocket::response::Responder<'r, 'o> for #ident {
-            fn respond_to(self, request: &'r ::rocket::Request<'_>) -> ::rocket::response::Result<'o> {
-                let status = match &self {
+        impl #ident {
+            /// The HTTP status this error responds with.
+            pub fn status(&self) -> ::rocket::http::Status {
+                match self {
                     #(#variant_response_status)*
                     _ => #response_status,
-                };
+                }
+            }
+        }
+
+        impl<'r, 'o: 'r> ::rocket::response::Responder<'r, 'o> for #ident {
+            fn respond_to(self, request: &'r ::rocket::Request<'_>) -> ::rocket::response::Result<'o> {
+                let status = self.status();
 
                 let mut res = ::rocket::serde::json::Json(Inner { error: self }).respond_to(request)?;
                 res.set_status(status);
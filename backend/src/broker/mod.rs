@@ -0,0 +1,280 @@
+mod paper;
+mod quote;
+
+use std::{fmt::Debug, sync::Arc, time::Duration};
+
+use chrono::{DateTime, TimeDelta, Utc};
+use rocket::fairing::{AdHoc, Fairing};
+use serde::{Deserialize, Serialize};
+
+pub use paper::PaperBroker;
+pub use quote::{
+    AssetClass, BrokerQuote, BrokerQuotes, FormattedQuotes, Quote, QuoteFormat, QuoteStatus,
+};
+
+use crate::{
+    errors::ApplicationError,
+    oauth::Credentials,
+    quotes::{QuotesConfig, QuotesState},
+    schwab::{
+        SchwabBroker,
+        client::{SchwabApiConfig, SchwabClient},
+        drift::FieldDrift,
+        limiter::RateLimitStatus,
+    },
+    symbol::Symbol,
+};
+
+/// The broker every endpoint talks to, as managed by [`fairing`].
+pub type DynBroker = Arc<dyn Broker>;
+
+/// Market data and account access for a single brokerage, in the broker-neutral
+/// types below.
+#[rocket::async_trait]
+pub trait Broker: Debug + Send + Sync + 'static {
+    async fn accounts(&self, credentials: &Credentials) -> Result<Vec<Account>, ApplicationError>;
+
+    /// Quotes for `symbols`, each in whichever form the broker sent it.
+    async fn quotes(
+        &self,
+        credentials: &Credentials,
+        symbols: Vec<Symbol>,
+    ) -> Result<BrokerQuotes, ApplicationError>;
+
+    async fn price_history(
+        &self,
+        credentials: &Credentials,
+        symbol: &str,
+        period: HistoryPeriod,
+    ) -> Result<PriceHistory, ApplicationError>;
+
+    async fn orders(
+        &self,
+        credentials: &Credentials,
+        account: &str,
+    ) -> Result<Vec<Order>, ApplicationError>;
+
+    /// Only the paper broker places orders; real brokers keep their accounts read-only.
+    async fn place_order(
+        &self,
+        _credentials: &Credentials,
+        _account: &str,
+        _order: OrderRequest,
+    ) -> Result<Order, ApplicationError> {
+        Err(ApplicationError::OrdersUnsupported)
+    }
+
+    /// The format quotes are sent in when a client doesn't ask for one. Only
+    /// Schwab's quotes can be sent in [`QuoteFormat::Schwab`].
+    fn quote_format(&self) -> QuoteFormat {
+        QuoteFormat::Normalized
+    }
+
+    /// Tokens left in each rate limit bucket, for brokers that have them.
+    fn rate_limits(&self) -> Option<RateLimitStatus> {
+        None
+    }
+
+    /// Response fields the broker sent that its schema doesn't know, for brokers that check.
+    fn schema_drift(&self) -> Option<Vec<FieldDrift>> {
+        None
+    }
+
+    /// How much longer quote pollers should wait than usual, to stay under the
+    /// broker's rate limits.
+    fn quote_pace(&self) -> Duration {
+        Duration::ZERO
+    }
+}
+
+/// A brokerage account the user can see.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Account {
+    pub account_number: String,
+    /// the name the user gave the account at their broker
+    pub nickname: String,
+    /// the account the broker shows first
+    pub primary: bool,
+    /// e.g. `BROKERAGE`, or `PAPER` for the paper broker
+    pub account_type: String,
+}
+
+/// How far back a price history reaches. Each period has a fixed candle width.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, FromFormField)]
+#[serde(rename_all = "snake_case")]
+pub enum HistoryPeriod {
+    /// one day of five minute candles
+    Day,
+    /// five days of thirty minute candles
+    Week,
+    /// one month of daily candles
+    Month,
+    /// one year of daily candles
+    Year,
+}
+
+impl HistoryPeriod {
+    pub fn candle_width(self) -> TimeDelta {
+        match self {
+            Self::Day => TimeDelta::minutes(5),
+            Self::Week => TimeDelta::minutes(30),
+            Self::Month | Self::Year => TimeDelta::days(1),
+        }
+    }
+
+    pub fn span(self) -> TimeDelta {
+        match self {
+            Self::Day => TimeDelta::days(1),
+            Self::Week => TimeDelta::days(5),
+            Self::Month => TimeDelta::days(30),
+            Self::Year => TimeDelta::days(365),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Candle {
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    pub volume: i64,
+    /// milliseconds since the epoch
+    pub datetime: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PriceHistory {
+    pub symbol: String,
+    pub candles: Vec<Candle>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OrderSide {
+    Buy,
+    Sell,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OrderType {
+    Market,
+    Limit,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OrderStatus {
+    Working,
+    Filled,
+    Canceled,
+    Rejected,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrderRequest {
+    pub symbol: String,
+    pub side: OrderSide,
+    pub quantity: f64,
+    pub order_type: OrderType,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit_price: Option<f64>,
+}
+
+impl OrderRequest {
+    /// Reject orders no broker would accept before spending a request on them.
+    pub fn validate(&self) -> Result<(), ApplicationError> {
+        if self.quantity <= 0.0 || !self.quantity.is_finite() {
+            return Err(ApplicationError::InvalidOrder(
+                "quantity must be positive".to_owned(),
+            ));
+        }
+
+        match (self.order_type, self.limit_price) {
+            (OrderType::Limit, None) => Err(ApplicationError::InvalidOrder(
+                "limit orders need a limit_price".to_owned(),
+            )),
+            (OrderType::Limit, Some(price)) if price <= 0.0 || !price.is_finite() => Err(
+                ApplicationError::InvalidOrder("limit_price must be positive".to_owned()),
+            ),
+            (OrderType::Market, Some(_)) => Err(ApplicationError::InvalidOrder(
+                "market orders do not take a limit_price".to_owned(),
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Order {
+    pub id: String,
+    pub symbol: String,
+    pub side: OrderSide,
+    pub quantity: f64,
+    pub filled_quantity: f64,
+    pub order_type: OrderType,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit_price: Option<f64>,
+    pub status: OrderStatus,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub entered_time: Option<DateTime<Utc>>,
+}
+
+/// Picks the broker from the `broker` config key (`schwab` or `paper`, defaulting
+/// to `schwab`) and manages it alongside the [`QuotesState`] that polls it.
+///
+/// The Schwab broker's API base URLs come from the `schwab` table; the paper broker
+/// is seeded from `paper_seed` so runs are reproducible.
+pub fn fairing() -> impl Fairing {
+    AdHoc::try_on_ignite("Broker", |rocket| async {
+        let figment = rocket.figment();
+
+        let broker: DynBroker = match figment.extract_inner::<String>("broker").as_deref() {
+            Ok("schwab") | Err(_) => {
+                let config = match figment.find_value("schwab") {
+                    Ok(_) => match figment.extract_inner::<SchwabApiConfig>("schwab") {
+                        Ok(config) => config,
+                        Err(e) => {
+                            error!("invalid `schwab` configuration: {e}");
+                            return Err(rocket);
+                        }
+                    },
+                    Err(_) => SchwabApiConfig::default(),
+                };
+
+                match SchwabClient::new(config) {
+                    Ok(client) => Arc::new(SchwabBroker::new(client)),
+                    Err(e) => {
+                        error!("could not build the Schwab HTTP client: {e}");
+                        return Err(rocket);
+                    }
+                }
+            }
+            Ok("paper") => {
+                let seed = figment.extract_inner::<u64>("paper_seed").unwrap_or(0);
+                info!("using the paper broker with seed {seed}");
+                Arc::new(PaperBroker::new(seed))
+            }
+            Ok(other) => {
+                error!("unknown broker `{other}`; expected `schwab` or `paper`");
+                return Err(rocket);
+            }
+        };
+
+        let quotes = match figment.find_value("quotes") {
+            Ok(_) => match figment.extract_inner::<QuotesConfig>("quotes") {
+                Ok(config) => config,
+                Err(e) => {
+                    error!("invalid `quotes` configuration: {e}");
+                    return Err(rocket);
+                }
+            },
+            Err(_) => QuotesConfig::default(),
+        };
+
+        Ok(rocket
+            .manage(QuotesState::new(broker.clone(), quotes))
+            .manage(broker))
+    })
+}
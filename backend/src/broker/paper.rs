@@ -0,0 +1,415 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+use chrono::{DurationRound, Utc};
+
+use crate::{
+    broker::{
+        Account, AssetClass, Broker, BrokerQuote, BrokerQuotes, Candle, HistoryPeriod, Order,
+        OrderRequest, OrderSide, OrderStatus, OrderType, PriceHistory, Quote, QuoteStatus,
+    },
+    errors::ApplicationError,
+    money::Money,
+    oauth::Credentials,
+    symbol::Symbol,
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn credentials() -> Credentials {
+        Credentials::new("access".to_owned(), "refresh".to_owned(), 1800, Utc::now())
+    }
+
+    fn last_price(response: &BrokerQuotes, symbol: &str) -> Money {
+        match &response.quotes[symbol] {
+            BrokerQuote::Normalized(quote) => quote.last.unwrap(),
+            other => panic!("expected a normalized quote, got {other:?}"),
+        }
+    }
+
+    #[rocket::async_test]
+    async fn same_seed_same_prices() {
+        let (a, b) = (PaperBroker::new(7), PaperBroker::new(7));
+        let symbols: Vec<Symbol> = vec!["AAPL".parse().unwrap(), "MSFT".parse().unwrap()];
+
+        for _ in 0..5 {
+            let qa = a.quotes(&credentials(), symbols.clone()).await.unwrap();
+            let qb = b.quotes(&credentials(), symbols.clone()).await.unwrap();
+
+            for symbol in &symbols {
+                assert_eq!(
+                    last_price(&qa, symbol.as_str()),
+                    last_price(&qb, symbol.as_str())
+                );
+            }
+        }
+
+        let history = |broker: PaperBroker| async move {
+            broker
+                .price_history(&credentials(), "AAPL", HistoryPeriod::Day)
+                .await
+                .unwrap()
+                .candles
+                .into_iter()
+                .map(|c| c.close)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            history(PaperBroker::new(7)).await,
+            history(PaperBroker::new(7)).await
+        );
+        assert_ne!(
+            history(PaperBroker::new(7)).await,
+            history(PaperBroker::new(8)).await
+        );
+    }
+
+    #[rocket::async_test]
+    async fn orders_fill_against_the_walk() {
+        let broker = PaperBroker::new(0);
+
+        let market = broker
+            .place_order(
+                &credentials(),
+                PAPER_ACCOUNT,
+                OrderRequest {
+                    symbol: "AAPL".to_owned(),
+                    side: OrderSide::Buy,
+                    quantity: 10.0,
+                    order_type: OrderType::Market,
+                    limit_price: None,
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(market.status, OrderStatus::Filled);
+
+        let limit = broker
+            .place_order(
+                &credentials(),
+                PAPER_ACCOUNT,
+                OrderRequest {
+                    symbol: "AAPL".to_owned(),
+                    side: OrderSide::Buy,
+                    quantity: 10.0,
+                    order_type: OrderType::Limit,
+                    limit_price: Some(0.01),
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(limit.status, OrderStatus::Working);
+
+        let orders = broker.orders(&credentials(), PAPER_ACCOUNT).await.unwrap();
+        assert_eq!(orders.len(), 2);
+
+        assert!(matches!(
+            broker.orders(&credentials(), "nope").await,
+            Err(ApplicationError::UnknownAccount(_))
+        ));
+    }
+}
+
+/// The only account the paper broker knows about.
+pub const PAPER_ACCOUNT: &str = "PAPER-0001";
+
+/// Largest move of a single tick, as a fraction of the price.
+const MAX_STEP: f64 = 0.002;
+
+/// splitmix64: a tiny, well-mixed hash so the walk is reproducible without an RNG dependency.
+fn mix(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+/// FNV-1a, which unlike `DefaultHasher` is stable across Rust releases.
+fn fnv1a(s: &str) -> u64 {
+    s.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// A deterministic random walk for one symbol.
+#[derive(Debug, Clone)]
+struct Walk {
+    stream: u64,
+    tick: u64,
+    close: f64,
+    last: f64,
+    high: f64,
+    low: f64,
+    volume: i64,
+}
+
+impl Walk {
+    fn new(seed: u64, symbol: &str) -> Self {
+        let stream = mix(seed ^ fnv1a(symbol));
+        // somewhere between $10 and $500
+        let close = 10.0 + (stream % 49_000) as f64 / 100.0;
+
+        Self {
+            stream,
+            tick: 0,
+            close,
+            last: close,
+            high: close,
+            low: close,
+            volume: 0,
+        }
+    }
+
+    /// A step in `[-1, 1)` for tick `tick` of stream `stream`.
+    fn step(stream: u64, tick: u64) -> f64 {
+        let bits = mix(stream.wrapping_add(tick)) >> 11;
+        (bits as f64 / (1u64 << 53) as f64) * 2.0 - 1.0
+    }
+
+    fn advance(&mut self) {
+        self.tick += 1;
+
+        let step = Self::step(self.stream, self.tick);
+        self.last = round_cents(self.last * (1.0 + MAX_STEP * step));
+        self.high = self.high.max(self.last);
+        self.low = self.low.min(self.last);
+        self.volume += 100 * (1 + (mix(self.stream ^ self.tick) % 50) as i64);
+    }
+
+    fn quote(&self, symbol: &str) -> Quote {
+        let now = Utc::now();
+        let net_change = round_cents(self.last - self.close);
+        let money = |price: f64| Money::from_f64(price).map(Money::round_cents);
+
+        Quote {
+            symbol: symbol.to_owned(),
+            asset_class: AssetClass::Equity,
+            realtime: true,
+            bid: money(self.last - 0.01),
+            ask: money(self.last + 0.01),
+            last: money(self.last),
+            mark: money(self.last),
+            change: money(net_change),
+            change_percent: Some(net_change / self.close * 100.0),
+            volume: Some(self.volume),
+            quote_time: Some(now),
+            trade_time: Some(now),
+            status: QuoteStatus::Normal,
+        }
+    }
+}
+
+fn round_cents(price: f64) -> f64 {
+    (price * 100.0).round() / 100.0
+}
+
+/// An offline broker with made-up prices.
+///
+/// Every symbol follows its own random walk derived from the seed, advancing one
+/// tick per quote request, so two paper brokers with the same seed see the same
+/// prices given the same requests. Orders fill as soon as the walk crosses them.
+#[derive(Debug)]
+pub struct PaperBroker {
+    seed: u64,
+    walks: Mutex<HashMap<String, Walk>>,
+    orders: Mutex<Vec<Order>>,
+    next_order_id: AtomicU64,
+}
+
+impl PaperBroker {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            walks: Mutex::default(),
+            orders: Mutex::default(),
+            next_order_id: AtomicU64::new(1),
+        }
+    }
+
+    fn last_price(&self, symbol: &str) -> f64 {
+        let walks = self.walks.lock().expect("paper walks lock poisoned");
+
+        walks
+            .get(symbol)
+            .map_or_else(|| Walk::new(self.seed, symbol).last, |walk| walk.last)
+    }
+
+    fn check_account(account: &str) -> Result<(), ApplicationError> {
+        if account == PAPER_ACCOUNT {
+            Ok(())
+        } else {
+            Err(ApplicationError::UnknownAccount(account.to_owned()))
+        }
+    }
+
+    /// Fill working orders on `symbol` that the price has crossed.
+    fn match_orders(&self, symbol: &str, price: f64) {
+        let mut orders = self.orders.lock().expect("paper orders lock poisoned");
+
+        for order in orders.iter_mut() {
+            if order.symbol == symbol
+                && order.status == OrderStatus::Working
+                && crosses(order, price)
+            {
+                order.status = OrderStatus::Filled;
+                order.filled_quantity = order.quantity;
+            }
+        }
+    }
+}
+
+/// Whether an order would fill at `price`.
+fn crosses(order: &Order, price: f64) -> bool {
+    match (order.order_type, order.limit_price, order.side) {
+        (OrderType::Market, ..) => true,
+        (OrderType::Limit, Some(limit), OrderSide::Buy) => price <= limit,
+        (OrderType::Limit, Some(limit), OrderSide::Sell) => price >= limit,
+        (OrderType::Limit, None, _) => false,
+    }
+}
+
+#[rocket::async_trait]
+impl Broker for PaperBroker {
+    async fn accounts(&self, _credentials: &Credentials) -> Result<Vec<Account>, ApplicationError> {
+        Ok(vec![Account {
+            account_number: PAPER_ACCOUNT.to_owned(),
+            nickname: "Paper Trading".to_owned(),
+            primary: true,
+            account_type: "PAPER".to_owned(),
+        }])
+    }
+
+    async fn quotes(
+        &self,
+        _credentials: &Credentials,
+        symbols: Vec<Symbol>,
+    ) -> Result<BrokerQuotes, ApplicationError> {
+        let mut quotes = HashMap::new();
+        let mut prices = vec![];
+
+        {
+            let mut walks = self.walks.lock().expect("paper walks lock poisoned");
+
+            for symbol in symbols.into_iter().map(String::from) {
+                let walk = walks
+                    .entry(symbol.clone())
+                    .or_insert_with(|| Walk::new(self.seed, &symbol));
+
+                walk.advance();
+                prices.push((symbol.clone(), walk.last));
+                quotes.insert(symbol.clone(), BrokerQuote::Normalized(walk.quote(&symbol)));
+            }
+        }
+
+        for (symbol, price) in prices {
+            self.match_orders(&symbol, price);
+        }
+
+        Ok(BrokerQuotes {
+            quotes,
+            errors: None,
+        })
+    }
+
+    async fn price_history(
+        &self,
+        _credentials: &Credentials,
+        symbol: &str,
+        period: HistoryPeriod,
+    ) -> Result<PriceHistory, ApplicationError> {
+        let width = period.candle_width();
+        let count = period.span().num_minutes() / width.num_minutes();
+
+        let end = Utc::now()
+            .duration_trunc(width)
+            .expect("candle widths should truncate any timestamp");
+
+        // separate stream per period so the day and year charts don't share a walk
+        let mut walk = Walk::new(self.seed ^ mix(period as u64), symbol);
+        let mut candles = Vec::with_capacity(count as usize);
+
+        for i in 0..count {
+            let open = walk.last;
+            walk.high = open;
+            walk.low = open;
+
+            for _ in 0..4 {
+                walk.advance();
+            }
+
+            candles.push(Candle {
+                open,
+                high: walk.high,
+                low: walk.low,
+                close: walk.last,
+                volume: walk.volume,
+                datetime: (end - width * (count - i) as i32).timestamp_millis(),
+            });
+
+            walk.volume = 0;
+        }
+
+        Ok(PriceHistory {
+            symbol: symbol.to_owned(),
+            candles,
+        })
+    }
+
+    async fn orders(
+        &self,
+        _credentials: &Credentials,
+        account: &str,
+    ) -> Result<Vec<Order>, ApplicationError> {
+        Self::check_account(account)?;
+
+        Ok(self
+            .orders
+            .lock()
+            .expect("paper orders lock poisoned")
+            .clone())
+    }
+
+    async fn place_order(
+        &self,
+        _credentials: &Credentials,
+        account: &str,
+        request: OrderRequest,
+    ) -> Result<Order, ApplicationError> {
+        Self::check_account(account)?;
+        request.validate()?;
+
+        let id = self.next_order_id.fetch_add(1, Ordering::Relaxed);
+
+        let mut order = Order {
+            id: format!("paper-{id}"),
+            symbol: request.symbol,
+            side: request.side,
+            quantity: request.quantity,
+            filled_quantity: 0.0,
+            order_type: request.order_type,
+            limit_price: request.limit_price,
+            status: OrderStatus::Working,
+            entered_time: Some(Utc::now()),
+        };
+
+        if crosses(&order, self.last_price(&order.symbol)) {
+            order.status = OrderStatus::Filled;
+            order.filled_quantity = order.quantity;
+        }
+
+        self.orders
+            .lock()
+            .expect("paper orders lock poisoned")
+            .push(order.clone());
+
+        Ok(order)
+    }
+}
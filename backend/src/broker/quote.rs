@@ -0,0 +1,533 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc, serde::ts_milliseconds_option};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    errors::ApplicationError,
+    money::Money,
+    schwab::schema::{
+        EquityResponse, ForexResponse, FutureOptionResponse, FutureResponse, IndexResponse,
+        MutualFundResponse, OptionResponse, QuoteError, QuoteResponse, QuoteResponseObject,
+    },
+    symbol::Symbol,
+};
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn money(amount: &str) -> Option<Money> {
+        Some(amount.parse().unwrap())
+    }
+
+    #[test]
+    fn normalizes_every_asset_class() {
+        let response: QuoteResponse = serde_json::from_value(json!({
+            "AAPL": {
+                "assetMainType": "EQUITY", "ssid": 1, "symbol": "AAPL", "realtime": true,
+                "quote": {
+                    "bidPrice": 199.99, "askPrice": 200.01, "lastPrice": 200.0, "mark": 200.0,
+                    "netChange": 1.5, "netPercentChange": 0.75, "totalVolume": 1000,
+                    "quoteTime": 1700000000000_i64, "tradeTime": 1700000000001_i64,
+                    "securityStatus": "Normal"
+                }
+            },
+            "$SPX": {
+                "assetMainType": "INDEX", "ssid": 2, "symbol": "$SPX", "realtime": false,
+                "quote": { "lastPrice": 6000.0, "netChange": -12.0, "securityStatus": "Closed" }
+            },
+            "VFIAX": {
+                "assetMainType": "MUTUAL_FUND", "ssid": 3, "symbol": "VFIAX", "realtime": true,
+                "quote": { "nAV": 550.25, "securityStatus": "Halted" }
+            },
+            "errors": { "invalidSymbols": ["NOPE"] }
+        }))
+        .unwrap();
+
+        let normalized = NormalizedQuotes::from(BrokerQuotes::from(response));
+        assert_eq!(normalized.invalid_symbols, ["NOPE"]);
+
+        let aapl = &normalized.quotes["AAPL"];
+        assert_eq!(aapl.asset_class, AssetClass::Equity);
+        assert_eq!(
+            (aapl.bid, aapl.ask, aapl.last),
+            (money("199.99"), money("200.01"), money("200"))
+        );
+        assert_eq!(aapl.change_percent, Some(0.75));
+        assert_eq!(aapl.volume, Some(1000));
+        assert_eq!(
+            aapl.trade_time,
+            DateTime::from_timestamp_millis(1700000000001)
+        );
+        assert_eq!(aapl.status, QuoteStatus::Normal);
+
+        let spx = &normalized.quotes["$SPX"];
+        assert_eq!(spx.asset_class, AssetClass::Index);
+        assert_eq!(spx.mark, money("6000"));
+        assert_eq!(spx.bid, None);
+        assert_eq!(spx.status, QuoteStatus::Closed);
+        assert!(!spx.realtime);
+
+        let fund = &normalized.quotes["VFIAX"];
+        assert_eq!(fund.asset_class, AssetClass::MutualFund);
+        assert_eq!((fund.last, fund.mark), (money("550.25"), money("550.25")));
+        assert_eq!(fund.status, QuoteStatus::Halted);
+    }
+
+    #[test]
+    fn schwab_format_passes_the_response_through() {
+        let response = json!({
+            "AAPL": {
+                "assetMainType": "EQUITY", "ssid": 1, "symbol": "AAPL", "realtime": true,
+                "quote": { "lastPrice": 200.0 }
+            },
+            "errors": {
+                "invalidSymbols": ["NOPE", "ALSO"], "invalidCusips": ["000000000"],
+                "invalidSSIDs": [7]
+            }
+        });
+        let quotes = BrokerQuotes::from(serde_json::from_value::<QuoteResponse>(response).unwrap());
+
+        let selected = quotes.select(&["AAPL".parse().unwrap(), "NOPE".parse().unwrap()]);
+        let FormattedQuotes::Schwab(schwab) = QuoteFormat::Schwab.apply(selected).unwrap() else {
+            panic!("expected Schwab's format");
+        };
+        assert_eq!(
+            serde_json::to_value(&schwab).unwrap()["errors"],
+            json!({
+                "invalidSymbols": ["NOPE"], "invalidCusips": ["000000000"], "invalidSSIDs": [7]
+            })
+        );
+    }
+
+    #[test]
+    fn only_schwab_quotes_come_in_schwab_format() {
+        let response: FutureResponse = serde_json::from_value(json!({
+            "assetMainType": "FUTURE", "ssid": 4, "symbol": "/ESZ25", "realtime": true
+        }))
+        .unwrap();
+
+        let quotes = BrokerQuotes {
+            quotes: HashMap::from([(
+                "/ESZ25".to_owned(),
+                BrokerQuote::Normalized(Quote::from(response)),
+            )]),
+            errors: None,
+        };
+
+        assert!(matches!(
+            QuoteFormat::Schwab.apply(quotes.clone()),
+            Err(ApplicationError::UnsupportedQuoteFormat(
+                QuoteFormat::Schwab
+            ))
+        ));
+        assert!(QuoteFormat::Normalized.apply(quotes).is_ok());
+    }
+
+    #[test]
+    fn missing_quote_blocks_are_empty() {
+        let response: FutureResponse = serde_json::from_value(json!({
+            "assetMainType": "FUTURE", "ssid": 4, "symbol": "/ESZ25", "realtime": true
+        }))
+        .unwrap();
+
+        let quote = Quote::from(response);
+        assert_eq!(quote.asset_class, AssetClass::Future);
+        assert_eq!(quote.last, None);
+        assert_eq!(quote.status, QuoteStatus::Unknown);
+    }
+}
+
+/// Which kind of instrument a [`Quote`] is for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AssetClass {
+    Equity,
+    Option,
+    Forex,
+    Future,
+    FutureOption,
+    Index,
+    MutualFund,
+}
+
+/// Whether the instrument is trading, as far as the broker knows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum QuoteStatus {
+    Normal,
+    Halted,
+    Closed,
+    Unknown,
+}
+
+impl QuoteStatus {
+    fn from_security_status(status: Option<&str>) -> Self {
+        match status.map(str::to_ascii_lowercase).as_deref() {
+            Some("normal") => Self::Normal,
+            Some("halted") => Self::Halted,
+            Some("closed") => Self::Closed,
+            _ => Self::Unknown,
+        }
+    }
+}
+
+/// One quote in the same shape for every asset class, so that clients don't
+/// need to know each of Schwab's response types.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Quote {
+    pub symbol: String,
+    pub asset_class: AssetClass,
+    /// false for delayed quotes
+    pub realtime: bool,
+    pub bid: Option<Money>,
+    pub ask: Option<Money>,
+    pub last: Option<Money>,
+    /// the price positions are valued at; the last price for instruments without a bid and ask
+    pub mark: Option<Money>,
+    /// since the previous close
+    pub change: Option<Money>,
+    pub change_percent: Option<f64>,
+    pub volume: Option<i64>,
+    /// milliseconds since the epoch, like Schwab's
+    #[serde(default, with = "ts_milliseconds_option")]
+    #[schemars(with = "Option<i64>")]
+    pub quote_time: Option<DateTime<Utc>>,
+    /// milliseconds since the epoch, like Schwab's
+    #[serde(default, with = "ts_milliseconds_option")]
+    #[schemars(with = "Option<i64>")]
+    pub trade_time: Option<DateTime<Utc>>,
+    pub status: QuoteStatus,
+}
+
+impl From<EquityResponse> for Quote {
+    fn from(value: EquityResponse) -> Self {
+        let quote = value.quote.unwrap_or_default();
+
+        Self {
+            symbol: value.symbol,
+            asset_class: AssetClass::Equity,
+            realtime: value.realtime,
+            bid: quote.bid_price,
+            ask: quote.ask_price,
+            last: quote.last_price,
+            mark: quote.mark,
+            change: quote.net_change,
+            change_percent: quote.net_percent_change,
+            volume: quote.total_volume,
+            quote_time: quote.quote_time,
+            trade_time: quote.trade_time,
+            status: QuoteStatus::from_security_status(quote.security_status.as_deref()),
+        }
+    }
+}
+
+impl From<OptionResponse> for Quote {
+    fn from(value: OptionResponse) -> Self {
+        let quote = value.quote.unwrap_or_default();
+
+        Self {
+            symbol: value.symbol,
+            asset_class: AssetClass::Option,
+            realtime: value.realtime,
+            bid: quote.bid_price,
+            ask: quote.ask_price,
+            last: quote.last_price,
+            mark: quote.mark,
+            change: quote.net_change,
+            change_percent: quote.net_percent_change,
+            volume: quote.total_volume,
+            quote_time: quote.quote_time,
+            trade_time: quote.trade_time,
+            status: QuoteStatus::from_security_status(quote.security_status.as_deref()),
+        }
+    }
+}
+
+impl From<ForexResponse> for Quote {
+    fn from(value: ForexResponse) -> Self {
+        let quote = value.quote.unwrap_or_default();
+
+        Self {
+            symbol: value.symbol,
+            asset_class: AssetClass::Forex,
+            realtime: value.realtime,
+            bid: quote.bid_price,
+            ask: quote.ask_price,
+            last: quote.last_price,
+            mark: quote.mark,
+            change: quote.net_change,
+            change_percent: quote.net_percent_change,
+            volume: quote.total_volume,
+            quote_time: quote.quote_time,
+            trade_time: quote.trade_time,
+            status: QuoteStatus::from_security_status(quote.security_status.as_deref()),
+        }
+    }
+}
+
+impl From<FutureResponse> for Quote {
+    fn from(value: FutureResponse) -> Self {
+        let quote = value.quote.unwrap_or_default();
+
+        Self {
+            symbol: value.symbol,
+            asset_class: AssetClass::Future,
+            realtime: value.realtime,
+            bid: quote.bid_price,
+            ask: quote.ask_price,
+            last: quote.last_price,
+            mark: quote.mark,
+            change: quote.net_change,
+            change_percent: quote.future_percent_change,
+            volume: quote.total_volume,
+            quote_time: quote.quote_time,
+            trade_time: quote.trade_time,
+            status: QuoteStatus::from_security_status(quote.security_status.as_deref()),
+        }
+    }
+}
+
+impl From<FutureOptionResponse> for Quote {
+    fn from(value: FutureOptionResponse) -> Self {
+        let quote = value.quote.unwrap_or_default();
+
+        Self {
+            symbol: value.symbol,
+            asset_class: AssetClass::FutureOption,
+            realtime: value.realtime,
+            bid: quote.bid_price,
+            ask: quote.ask_price,
+            last: quote.last_price,
+            mark: quote.mark,
+            change: quote.net_change,
+            change_percent: quote.net_percent_change,
+            volume: quote.total_volume,
+            quote_time: quote.quote_time,
+            trade_time: quote.trade_time,
+            status: QuoteStatus::from_security_status(quote.security_status.as_deref()),
+        }
+    }
+}
+
+impl From<IndexResponse> for Quote {
+    fn from(value: IndexResponse) -> Self {
+        let quote = value.quote.unwrap_or_default();
+
+        Self {
+            symbol: value.symbol,
+            asset_class: AssetClass::Index,
+            realtime: value.realtime,
+            bid: None,
+            ask: None,
+            last: quote.last_price,
+            mark: quote.last_price,
+            change: quote.net_change,
+            change_percent: quote.net_percent_change,
+            volume: quote.total_volume,
+            quote_time: None,
+            trade_time: quote.trade_time,
+            status: QuoteStatus::from_security_status(quote.security_status.as_deref()),
+        }
+    }
+}
+
+impl From<MutualFundResponse> for Quote {
+    /// Funds trade once a day at their net asset value, which stands in for every price.
+    fn from(value: MutualFundResponse) -> Self {
+        let quote = value.quote.unwrap_or_default();
+
+        Self {
+            symbol: value.symbol,
+            asset_class: AssetClass::MutualFund,
+            realtime: value.realtime,
+            bid: None,
+            ask: None,
+            last: quote.nav,
+            mark: quote.nav,
+            change: quote.net_change,
+            change_percent: quote.net_percent_change,
+            volume: quote.total_volume,
+            quote_time: None,
+            trade_time: quote.trade_time,
+            status: QuoteStatus::from_security_status(quote.security_status.as_deref()),
+        }
+    }
+}
+
+/// A quote in the form the broker sent it.
+///
+/// Only this one form is kept, cached and broadcast; the other [`QuoteFormat`]
+/// is built from it when a client asks for that.
+#[derive(Debug, Clone)]
+pub enum BrokerQuote {
+    /// one of Schwab's per-asset-class responses, never its error entry
+    Schwab(Box<QuoteResponseObject>),
+    /// from brokers without a wire format of their own
+    Normalized(Quote),
+}
+
+impl BrokerQuote {
+    fn normalize(self) -> Option<Quote> {
+        let object = match self {
+            Self::Normalized(quote) => return Some(quote),
+            Self::Schwab(object) => *object,
+        };
+
+        let quote = match object {
+            QuoteResponseObject::Equity(response) => Quote::from(response),
+            QuoteResponseObject::Option(response) => Quote::from(response),
+            QuoteResponseObject::Forex(response) => Quote::from(response),
+            QuoteResponseObject::Future(response) => Quote::from(response),
+            QuoteResponseObject::FutureOption(response) => Quote::from(response),
+            QuoteResponseObject::Index(response) => Quote::from(response),
+            QuoteResponseObject::MutualFund(response) => Quote::from(response),
+            QuoteResponseObject::Error(_) => return None,
+        };
+
+        Some(quote)
+    }
+}
+
+/// Quotes from any broker, as it sent them.
+#[derive(Debug, Clone, Default)]
+pub struct BrokerQuotes {
+    pub quotes: HashMap<String, BrokerQuote>,
+    /// Schwab's error entry as sent; other brokers only fill in `invalid_symbols`
+    pub errors: Option<QuoteError>,
+}
+
+impl From<QuoteResponse> for BrokerQuotes {
+    fn from(value: QuoteResponse) -> Self {
+        let mut quotes = Self::default();
+
+        for (symbol, object) in value.quotes {
+            match object {
+                QuoteResponseObject::Error(error) => quotes.errors = Some(error),
+                object => {
+                    quotes
+                        .quotes
+                        .insert(symbol, BrokerQuote::Schwab(Box::new(object)));
+                }
+            }
+        }
+
+        quotes
+    }
+}
+
+impl BrokerQuotes {
+    /// Only the entries for `symbols`. Schwab's invalid CUSIPs and SSIDs aren't
+    /// tied to a symbol, so they are kept either way.
+    pub fn select(&self, symbols: &[Symbol]) -> Self {
+        let wanted = |symbol: &str| symbols.iter().any(|wanted| wanted.as_str() == symbol);
+
+        let errors = self.errors.as_ref().map(|errors| QuoteError {
+            invalid_symbols: errors
+                .invalid_symbols
+                .iter()
+                .filter(|symbol| wanted(symbol))
+                .cloned()
+                .collect(),
+            ..errors.clone()
+        });
+
+        Self {
+            quotes: self
+                .quotes
+                .iter()
+                .filter(|(symbol, _)| wanted(symbol))
+                .map(|(symbol, quote)| (symbol.clone(), quote.clone()))
+                .collect(),
+            errors,
+        }
+    }
+
+    /// Schwab's `QuoteResponse`, unless some quote came from another broker.
+    fn into_schwab(self) -> Option<QuoteResponse> {
+        let mut quotes = self
+            .quotes
+            .into_iter()
+            .map(|(symbol, quote)| match quote {
+                BrokerQuote::Schwab(object) => Some((symbol, *object)),
+                BrokerQuote::Normalized(_) => None,
+            })
+            .collect::<Option<HashMap<_, _>>>()?;
+
+        if let Some(errors) = self.errors {
+            quotes.insert("errors".to_owned(), QuoteResponseObject::Error(errors));
+        }
+
+        Some(QuoteResponse { quotes })
+    }
+}
+
+/// Quotes in the same shape for every broker and asset class.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct NormalizedQuotes {
+    pub quotes: HashMap<String, Quote>,
+    /// symbols the broker did not recognize
+    pub invalid_symbols: Vec<String>,
+}
+
+impl From<BrokerQuotes> for NormalizedQuotes {
+    fn from(value: BrokerQuotes) -> Self {
+        Self {
+            quotes: value
+                .quotes
+                .into_iter()
+                .filter_map(|(symbol, quote)| Some((symbol, quote.normalize()?)))
+                .collect(),
+            invalid_symbols: value
+                .errors
+                .map(|errors| errors.invalid_symbols)
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// How quotes are sent to clients, chosen with `?format=` on `/u/quotes` and its stream.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, FromFormField)]
+#[serde(rename_all = "snake_case")]
+pub enum QuoteFormat {
+    /// Schwab's own response types, one per asset class; only for quotes from Schwab
+    #[default]
+    Schwab,
+    /// [`NormalizedQuotes`]
+    Normalized,
+}
+
+/// Quotes in a [`QuoteFormat`].
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+#[serde(untagged)]
+pub enum FormattedQuotes {
+    Schwab(QuoteResponse),
+    Normalized(NormalizedQuotes),
+}
+
+impl QuoteFormat {
+    /// The `requested` format, or the broker's `native` one when the client
+    /// didn't ask. Schwab's format is only offered by brokers whose native one it is.
+    pub fn negotiate(requested: Option<Self>, native: Self) -> Result<Self, ApplicationError> {
+        match requested.unwrap_or(native) {
+            Self::Schwab if native != Self::Schwab => {
+                Err(ApplicationError::UnsupportedQuoteFormat(Self::Schwab))
+            }
+            format => Ok(format),
+        }
+    }
+
+    pub fn apply(self, quotes: BrokerQuotes) -> Result<FormattedQuotes, ApplicationError> {
+        match self {
+            Self::Schwab => quotes
+                .into_schwab()
+                .map(FormattedQuotes::Schwab)
+                .ok_or(ApplicationError::UnsupportedQuoteFormat(self)),
+            Self::Normalized => Ok(FormattedQuotes::Normalized(quotes.into())),
+        }
+    }
+}
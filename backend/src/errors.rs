@@ -1,12 +1,195 @@
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
 use error_responder::ErrorResponder;
-use rocket::tokio::sync::broadcast;
+use rocket::{Catcher, Request, http::Status, request::Outcome, serde::json::Json};
 use serde::Serialize;
 use serde_json::Value;
 use thiserror::Error;
 
-use crate::oauth::CredentialsError;
+use crate::{broker::QuoteFormat, oauth::CredentialsError, symbol::InvalidSymbol};
+
+#[cfg(test)]
+mod tests {
+    use rocket::{local::asynchronous::Client, response::Responder};
+    use serde_json::json;
+
+    use super::*;
+    use crate::symbol::SymbolError;
+
+    async fn render(client: &Client, error: ApplicationError) -> (Status, Value) {
+        let request = client.get("/");
+        let mut response = error
+            .respond_to(request.inner())
+            .expect("errors should always render");
+
+        let body = response.body_mut().to_string().await.unwrap();
+        (response.status(), serde_json::from_str(&body).unwrap())
+    }
+
+    #[rocket::async_test]
+    async fn every_variant_renders_json() {
+        let client = Client::debug_with(vec![]).await.unwrap();
+
+        let serde_error = || serde_json::from_str::<Value>("{").unwrap_err();
+        let network_error = reqwest::Client::new().get("not a url").build().unwrap_err();
+
+        let cases = [
+            (
+                ApplicationError::MissingAuthentication,
+                Status::Unauthorized,
+                json!("MissingAuthentication"),
+            ),
+            (
+                ApplicationError::InvalidCredentials(CredentialsError::RefreshTokenExpired),
+                Status::Unauthorized,
+                json!("InvalidCredentials"),
+            ),
+            (
+                ApplicationError::Network(network_error),
+                Status::InternalServerError,
+                json!("Network"),
+            ),
+            (
+                ApplicationError::InvalidJson(serde_error()),
+                Status::InternalServerError,
+                json!("InvalidJson"),
+            ),
+            (
+                ApplicationError::InvalidJsonLookup {
+                    index: "accounts".to_owned(),
+                    object: json!({}),
+                },
+                Status::InternalServerError,
+                json!({ "InvalidJsonLookup": { "index": "accounts", "object": {} } }),
+            ),
+            (
+                ApplicationError::SchwabAccountDeserialization(serde_error()),
+                Status::InternalServerError,
+                json!("SchwabAccountDeserialization"),
+            ),
+            (
+                ApplicationError::QuoteResponseDeserialization(serde_error()),
+                Status::InternalServerError,
+                json!("QuoteResponseDeserialization"),
+            ),
+            (
+                ApplicationError::Polling(Arc::new(ApplicationError::MissingAuthentication)),
+                Status::InternalServerError,
+                json!({ "Polling": "MissingAuthentication" }),
+            ),
+            (
+                ApplicationError::Database(db::DbErr::Custom("locked".to_owned())),
+                Status::InternalServerError,
+                json!("Database"),
+            ),
+            (
+                ApplicationError::InvalidSymbol(InvalidSymbol {
+                    symbol: "/ESA25".to_owned(),
+                    reason: SymbolError::InvalidMonthCode('A'),
+                }),
+                Status::BadRequest,
+                json!({
+                    "InvalidSymbol": {
+                        "symbol": "/ESA25",
+                        "reason": { "InvalidMonthCode": "A" },
+                    }
+                }),
+            ),
+            (
+                ApplicationError::InvalidRedirect("//evil".to_owned()),
+                Status::BadRequest,
+                json!({ "InvalidRedirect": "//evil" }),
+            ),
+            (
+                ApplicationError::InvalidOrder("quantity must be positive".to_owned()),
+                Status::BadRequest,
+                json!({ "InvalidOrder": "quantity must be positive" }),
+            ),
+            (
+                ApplicationError::OrdersUnsupported,
+                Status::NotImplemented,
+                json!("OrdersUnsupported"),
+            ),
+            (
+                ApplicationError::UnknownAccount("1234".to_owned()),
+                Status::NotFound,
+                json!({ "UnknownAccount": "1234" }),
+            ),
+            (
+                ApplicationError::UnsupportedQuoteFormat(QuoteFormat::Schwab),
+                Status::BadRequest,
+                json!({ "UnsupportedQuoteFormat": "schwab" }),
+            ),
+            (
+                ApplicationError::MissingQueryParameters(vec!["symbols".to_owned()]),
+                Status::BadRequest,
+                json!({ "MissingQueryParameters": ["symbols"] }),
+            ),
+            (
+                ApplicationError::UpstreamUnauthorized,
+                Status::Unauthorized,
+                json!("UpstreamUnauthorized"),
+            ),
+            (
+                ApplicationError::UpstreamBadRequest {
+                    detail: "Search combination should have min of 1.".to_owned(),
+                    pointers: vec!["/data/attributes/symbols".to_owned()],
+                },
+                Status::BadRequest,
+                json!({
+                    "UpstreamBadRequest": {
+                        "detail": "Search combination should have min of 1.",
+                        "pointers": ["/data/attributes/symbols"],
+                    }
+                }),
+            ),
+            (
+                ApplicationError::UpstreamRateLimited,
+                Status::TooManyRequests,
+                json!("UpstreamRateLimited"),
+            ),
+            (
+                ApplicationError::RateLimited {
+                    retry_after_ms: 250,
+                },
+                Status::TooManyRequests,
+                json!({ "RateLimited": { "retry_after_ms": 250 } }),
+            ),
+            (
+                ApplicationError::UpstreamUnavailable {
+                    retry_after_ms: 5000,
+                },
+                Status::ServiceUnavailable,
+                json!({ "UpstreamUnavailable": { "retry_after_ms": 5000 } }),
+            ),
+            (
+                ApplicationError::Retried {
+                    attempts: 3,
+                    error: Box::new(ApplicationError::UpstreamRateLimited),
+                },
+                Status::TooManyRequests,
+                json!({ "Retried": { "attempts": 3, "error": "UpstreamRateLimited" } }),
+            ),
+            (
+                ApplicationError::UpstreamFailed {
+                    status: 500,
+                    detail: "Internal error".to_owned(),
+                },
+                Status::BadGateway,
+                json!({ "UpstreamFailed": { "status": 500, "detail": "Internal error" } }),
+            ),
+        ];
+
+        for (error, status, body) in cases {
+            let name = format!("{error:?}");
+            assert_eq!(
+                render(&client, error).await,
+                (status, json!({ "error": body })),
+                "{name}"
+            );
+        }
+    }
+}
 
 #[derive(ErrorResponder, Error, Serialize, Debug)]
 #[serde(crate = "rocket::serde")]
@@ -15,13 +198,6 @@ pub enum ApplicationError {
     #[respond("Unauthorized")]
     MissingAuthentication,
 
-    #[error(
-        "invalid WebSocket payload; expected JSON control message of the form: \
-         {{\"type\":\"add|remove\",\"symbols\":[\"AAPL\",\"MSFT\",...]}}"
-    )]
-    #[respond("BadRequest")]
-    InvalidWebSocketPayload,
-
     #[error("invalid credentials string: {0}")]
     #[respond("Unauthorized")]
     InvalidCredentials(
@@ -66,20 +242,130 @@ pub enum ApplicationError {
         serde_json::Error,
     ),
 
-    #[error("channel failed: {0}")]
-    #[respond("InternalServerError")]
-    ChannelBroadcastFailed(
-        #[from(broadcast::error::RecvError)]
-        #[serde(skip)]
-        broadcast::error::RecvError,
-    ),
-
     /// Allows for many channels to share an ApplicationError from broadcast
     #[error("shared error in polling: {0}")]
     #[respond("InternalServerError")]
     Polling(Arc<ApplicationError>),
 
+    #[error("session store failed: {0}")]
+    #[respond("InternalServerError")]
+    Database(
+        #[from(db::DbErr)]
+        #[serde(skip)]
+        db::DbErr,
+    ),
+
+    #[error("post-login redirect is not allowlisted: {0}")]
+    #[respond("BadRequest")]
+    InvalidRedirect(String),
+
+    #[error("invalid order: {0}")]
+    #[respond("BadRequest")]
+    InvalidOrder(String),
+
+    #[error("this broker does not place orders")]
+    #[respond("NotImplemented")]
+    OrdersUnsupported,
+
+    #[error("no such account: {0}")]
+    #[respond("NotFound")]
+    UnknownAccount(String),
+
+    #[error("quotes from this broker can't be sent in the {0:?} format")]
+    #[respond("BadRequest")]
+    UnsupportedQuoteFormat(QuoteFormat),
+
+    #[error("{0}")]
+    #[respond("BadRequest")]
+    InvalidSymbol(InvalidSymbol),
+
     #[error("missing required query parameters: {0:?}")]
     #[respond("BadRequest")]
     MissingQueryParameters(Vec<String>),
+
+    #[error("Schwab rejected the access token")]
+    #[respond("Unauthorized")]
+    UpstreamUnauthorized,
+
+    #[error("Schwab rejected the request: {detail}")]
+    #[respond("BadRequest")]
+    UpstreamBadRequest {
+        detail: String,
+        pointers: Vec<String>,
+    },
+
+    #[error("Schwab is rate limiting requests")]
+    #[respond("TooManyRequests")]
+    UpstreamRateLimited,
+
+    /// Shed by our own rate limiter before reaching Schwab
+    #[error("too many Schwab requests; retry in {retry_after_ms}ms")]
+    #[respond("TooManyRequests")]
+    RateLimited { retry_after_ms: u64 },
+
+    /// Schwab has been failing, so requests are held back until the circuit breaker closes
+    #[error("Schwab is unavailable; retry in {retry_after_ms}ms")]
+    #[respond("ServiceUnavailable")]
+    UpstreamUnavailable { retry_after_ms: u64 },
+
+    /// An idempotent Schwab request that kept failing, however often it was retried
+    #[error("{error} (gave up after {attempts} attempts)")]
+    #[respond(from = "error")]
+    Retried {
+        attempts: u32,
+        error: Box<ApplicationError>,
+    },
+
+    /// Any other non-2xx answer, e.g. a Schwab outage
+    #[error("Schwab responded {status}: {detail}")]
+    #[respond("BadGateway")]
+    UpstreamFailed { status: u16, detail: String },
+}
+
+/// The body of an error raised by a request guard, held until the catcher renders it.
+///
+/// Rocket hands catchers only the status of a failed guard, so guards stash the
+/// serialized [`ApplicationError`] here to keep the JSON consistent with handler errors.
+#[derive(Default)]
+struct DeferredError(Mutex<Option<Value>>);
+
+impl ApplicationError {
+    /// Fail a request guard with this error.
+    pub fn defer<S>(self, request: &Request<'_>) -> Outcome<S, ApplicationError> {
+        let body = serde_json::json!({ "error": &self });
+
+        let deferred = request.local_cache(DeferredError::default);
+        *deferred.0.lock().expect("deferred error lock poisoned") = Some(body);
+
+        Outcome::Error((self.status(), self))
+    }
+}
+
+fn take_deferred(request: &Request<'_>) -> Option<Value> {
+    let deferred = request.local_cache(DeferredError::default);
+    deferred
+        .0
+        .lock()
+        .expect("deferred error lock poisoned")
+        .take()
+}
+
+#[catch(401)]
+fn unauthorized(request: &Request<'_>) -> Json<Value> {
+    Json(
+        take_deferred(request).unwrap_or_else(
+            || serde_json::json!({ "error": ApplicationError::MissingAuthentication }),
+        ),
+    )
+}
+
+#[catch(500)]
+fn internal_server_error(request: &Request<'_>) -> Result<Json<Value>, Status> {
+    take_deferred(request)
+        .map(Json)
+        .ok_or(Status::InternalServerError)
+}
+
+pub fn catchers() -> Vec<Catcher> {
+    catchers![unauthorized, internal_server_error]
 }
@@ -0,0 +1,64 @@
+use std::path::Path;
+
+use rocket::{Build, Rocket, fs::FileServer, response::content::RawHtml};
+
+mod broker;
+mod errors;
+mod money;
+mod oauth;
+#[cfg(feature = "debug-routes")]
+mod pages;
+mod quotes;
+mod schwab;
+mod session;
+mod symbol;
+
+#[macro_use]
+extern crate rocket;
+
+const BUILD_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/build");
+
+/// See
+#[get("/<_..>", rank = 10)]
+async fn spa_fallback() -> Option<RawHtml<String>> {
+    let index_path = Path::new(BUILD_DIR).join("index.html");
+    let html = rocket::tokio::fs::read_to_string(index_path).await.ok()?;
+    Some(RawHtml(html))
+}
+
+/// The whole backend, configured from Rocket's usual sources.
+///
+/// Call [`Rocket::configure`] on the result to point it elsewhere, e.g. at a mock
+/// Schwab server in tests.
+pub fn rocket() -> Rocket<Build> {
+    let rocket = rocket::build()
+        .mount(
+            "/u",
+            routes![
+                oauth::schwab_login,
+                oauth::schwab_callback,
+                oauth::session_status,
+                oauth::logout,
+                schwab::endpoints::user,
+                schwab::endpoints::rate_limit,
+                schwab::endpoints::schema_drift,
+                schwab::endpoints::history,
+                schwab::endpoints::orders,
+                schwab::endpoints::place_order,
+                schwab::endpoints::quotes_stream,
+                schwab::endpoints::quotes_stream_schema,
+                schwab::endpoints::quotes
+            ],
+        )
+        .mount("/", FileServer::from(BUILD_DIR).rank(9))
+        .mount("/", routes![spa_fallback])
+        .register("/", errors::catchers())
+        .attach(oauth::fairing())
+        .attach(session::fairing())
+        .attach(broker::fairing());
+
+    #[cfg(feature = "debug-routes")]
+    let rocket = rocket.attach(pages::fairing());
+
+    rocket
+}
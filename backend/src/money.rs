@@ -0,0 +1,174 @@
+use std::{
+    borrow::Cow,
+    fmt,
+    iter::Sum,
+    ops::{Add, Neg, Sub},
+    str::FromStr,
+};
+
+use rust_decimal::{Decimal, prelude::ToPrimitive};
+use schemars::{JsonSchema, Schema, SchemaGenerator, json_schema};
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn money(amount: &str) -> Money {
+        amount.parse().unwrap()
+    }
+
+    #[test]
+    fn sums_do_not_drift() {
+        let prices: Vec<Money> = serde_json::from_str("[0.1, 0.2, 199.99, -0.3]").unwrap();
+
+        assert_eq!(prices.iter().copied().sum::<Money>(), money("199.99"));
+        assert_eq!(money("0.1") + money("0.2"), money("0.3"));
+        assert_eq!(-(money("1.5") - money("2")), money("0.5"));
+    }
+
+    #[test]
+    fn wire_format_is_a_number() {
+        for json in ["199.99", "0.0", "42", "-12.5", "0.0001"] {
+            let amount: Money = serde_json::from_str(json).unwrap();
+            let again: f64 =
+                serde_json::from_str(&serde_json::to_string(&amount).unwrap()).unwrap();
+            assert_eq!(again, json.parse::<f64>().unwrap(), "{json}");
+        }
+
+        assert!(serde_json::from_str::<Money>("\"12\"").is_err());
+        assert_eq!(Money::from_f64(f64::NAN), None);
+        assert_eq!(
+            Money::from_f64(2.675).map(Money::round_cents),
+            Some(money("2.68"))
+        );
+    }
+}
+
+/// A fixed-point decimal amount, e.g. a price, so that sums and differences
+/// come out exact instead of picking up float drift.
+///
+/// Read and written as a plain JSON number, the way Schwab sends prices.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Money(Decimal);
+
+impl Money {
+    pub const ZERO: Self = Self(Decimal::ZERO);
+
+    /// `value` by its shortest decimal representation, so `0.1` is exactly 0.1;
+    /// `None` for NaN, infinities and values out of range.
+    pub fn from_f64(value: f64) -> Option<Self> {
+        if !value.is_finite() {
+            return None;
+        }
+
+        Decimal::from_str(&value.to_string()).ok().map(Self)
+    }
+
+    /// The nearest `f64`, for math that doesn't need to be exact.
+    pub fn to_f64(self) -> f64 {
+        self.0
+            .to_f64()
+            .expect("every decimal is within f64's range")
+    }
+
+    /// Rounded half away from zero to whole cents.
+    pub fn round_cents(self) -> Self {
+        Self(
+            self.0
+                .round_dp_with_strategy(2, rust_decimal::RoundingStrategy::MidpointAwayFromZero),
+        )
+    }
+}
+
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl FromStr for Money {
+    type Err = rust_decimal::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Decimal::from_str(s).map(Self)
+    }
+}
+
+impl Add for Money {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self(self.0 + rhs.0)
+    }
+}
+
+impl Sub for Money {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        Self(self.0 - rhs.0)
+    }
+}
+
+impl Neg for Money {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self(-self.0)
+    }
+}
+
+impl Sum for Money {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Self::ZERO, Add::add)
+    }
+}
+
+impl Serialize for Money {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_f64(self.to_f64())
+    }
+}
+
+impl JsonSchema for Money {
+    fn inline_schema() -> bool {
+        true
+    }
+
+    fn schema_name() -> Cow<'static, str> {
+        "Money".into()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        json_schema!({ "type": "number" })
+    }
+}
+
+impl<'de> Deserialize<'de> for Money {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct MoneyVisitor;
+
+        impl de::Visitor<'_> for MoneyVisitor {
+            type Value = Money;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a number")
+            }
+
+            fn visit_i64<E: de::Error>(self, v: i64) -> Result<Money, E> {
+                Ok(Money(v.into()))
+            }
+
+            fn visit_u64<E: de::Error>(self, v: u64) -> Result<Money, E> {
+                Ok(Money(v.into()))
+            }
+
+            fn visit_f64<E: de::Error>(self, v: f64) -> Result<Money, E> {
+                Money::from_f64(v).ok_or_else(|| E::custom(format!("{v} is out of range")))
+            }
+        }
+
+        deserializer.deserialize_any(MoneyVisitor)
+    }
+}
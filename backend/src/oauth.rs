@@ -1,17 +1,135 @@
-use std::fmt::Debug;
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use base64::{Engine, prelude::BASE64_URL_SAFE_NO_PAD};
 use chrono::{DateTime, TimeDelta, Utc};
+use lazy_static::lazy_static;
+use reqwest::Client;
 use rocket::{
-    fairing::Fairing,
+    State,
+    fairing::{AdHoc, Fairing},
     http::{Cookie, CookieJar, SameSite},
     response::Redirect,
+    serde::json::Json,
+    tokio::sync::{Mutex, OnceCell},
 };
 use rocket_oauth2::{OAuth2, TokenResponse};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use thiserror::Error;
 
-pub(crate) static AUTH_COOKIE_NAME: &str = "__Host-auth";
+use crate::{
+    errors::ApplicationError,
+    quotes::QuotesState,
+    session::{AuthenticatedUser, SESSION_COOKIE_NAME, SessionId, SessionStore},
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn minted_ago(age: TimeDelta) -> Credentials {
+        Credentials::new(
+            "access".to_owned(),
+            "refresh".to_owned(),
+            1800,
+            Utc::now() - age,
+        )
+    }
+
+    #[test]
+    fn refresh_token_lifetime() {
+        let fresh = minted_ago(TimeDelta::hours(1));
+        assert!(!fresh.is_refresh_token_expiring());
+        assert!(!fresh.is_refresh_token_expired());
+
+        let expiring = minted_ago(TimeDelta::days(6) + TimeDelta::hours(1));
+        assert!(expiring.is_refresh_token_expiring());
+        assert!(!expiring.is_refresh_token_expired());
+
+        let expired = minted_ago(TimeDelta::days(8));
+        assert!(expired.is_refresh_token_expired());
+        assert!(expired.refresh_token_expires_in() < TimeDelta::zero());
+    }
+
+    #[test]
+    fn credentials_without_refresh_minted_decode() {
+        let blob = BASE64_URL_SAFE_NO_PAD.encode(
+            r#"{"access_token":"access","refresh_token":"refresh","expires_in":1800,"minted":1760000000}"#,
+        );
+
+        let credentials = Credentials::decode(&blob).unwrap();
+        assert_eq!(credentials.minted.timestamp(), 1_760_000_000);
+        assert_eq!(credentials.refresh_minted, credentials.minted);
+    }
+
+    fn token_response(json: Value) -> TokenResponse<Schwab> {
+        TokenResponse::try_from(json).unwrap().cast()
+    }
+
+    #[test]
+    fn malformed_token_responses_are_errors() {
+        let response = |extra: Value| {
+            let mut json = serde_json::json!({ "access_token": "access", "token_type": "Bearer" });
+            json.as_object_mut()
+                .unwrap()
+                .extend(extra.as_object().unwrap().clone());
+            Credentials::try_from(token_response(json))
+        };
+
+        let credentials =
+            response(serde_json::json!({ "refresh_token": "refresh", "expires_in": 1800 }))
+                .unwrap();
+        assert_eq!(credentials.access_token(), Some("access"));
+
+        for extra in [
+            serde_json::json!({ "expires_in": 1800 }),
+            serde_json::json!({ "refresh_token": "refresh" }),
+            serde_json::json!({ "refresh_token": "refresh", "expires_in": 0 }),
+            serde_json::json!({ "refresh_token": "refresh", "expires_in": -5 }),
+        ] {
+            assert!(
+                matches!(
+                    response(extra.clone()),
+                    Err(CredentialsError::InvalidTokenResponse(_))
+                ),
+                "{extra}"
+            );
+        }
+    }
+
+    #[test]
+    fn login_redirect_allowlist() {
+        let redirects = LoginRedirects {
+            default_redirect: "/".to_owned(),
+            allowed_redirects: vec!["/".to_owned(), "https://127.0.0.1:5173".to_owned()],
+        };
+
+        assert!(redirects.is_allowed("/debug/authenticated"));
+        assert!(redirects.is_allowed("https://127.0.0.1:5173"));
+        assert!(redirects.is_allowed("https://127.0.0.1:5173/dashboard?tab=1"));
+
+        assert!(!redirects.is_allowed("//evil.example"));
+        assert!(!redirects.is_allowed("/\\evil.example"));
+        assert!(!redirects.is_allowed("https://127.0.0.1:5173.evil.example"));
+        assert!(!redirects.is_allowed("https://evil.example/"));
+
+        assert_eq!(
+            redirects.resolve(Some("https://evil.example/".to_owned())),
+            "/"
+        );
+    }
+}
+
+pub(crate) static NEXT_COOKIE_NAME: &str = "__Host-login-next";
+
+/// Where rocket_oauth2 keeps the state of a login in progress.
+const OAUTH2_STATE_COOKIE_NAME: &str = "rocket_oauth2_state";
 
 pub struct Schwab;
 
@@ -21,6 +139,7 @@ pub struct CredentialsPackaged {
 }
 
 #[derive(Clone, Serialize, Deserialize)]
+#[serde(from = "StoredCredentials")]
 pub struct Credentials {
     access_token: String,
     refresh_token: String,
@@ -29,8 +148,42 @@ pub struct Credentials {
     /// serialized in seconds
     #[serde(with = "chrono::serde::ts_seconds")]
     minted: DateTime<Utc>,
+    /// when the current refresh token was issued; serialized in seconds
+    #[serde(with = "chrono::serde::ts_seconds")]
+    refresh_minted: DateTime<Utc>,
 }
 
+/// [`Credentials`] as stored by any version, including those from before `refresh_minted`.
+#[derive(Deserialize)]
+struct StoredCredentials {
+    access_token: String,
+    refresh_token: String,
+    expires_in: i64,
+    #[serde(with = "chrono::serde::ts_seconds")]
+    minted: DateTime<Utc>,
+    /// missing from older blobs, whose refresh token dates from the same mint
+    #[serde(default, with = "chrono::serde::ts_seconds_option")]
+    refresh_minted: Option<DateTime<Utc>>,
+}
+
+impl From<StoredCredentials> for Credentials {
+    fn from(value: StoredCredentials) -> Self {
+        Self {
+            access_token: value.access_token,
+            refresh_token: value.refresh_token,
+            expires_in: value.expires_in,
+            minted: value.minted,
+            refresh_minted: value.refresh_minted.unwrap_or(value.minted),
+        }
+    }
+}
+
+/// Schwab refresh tokens are only good for seven days, after which the user must log in again.
+pub const REFRESH_TOKEN_LIFETIME: TimeDelta = TimeDelta::days(7);
+
+/// How far ahead of refresh token expiry clients are told to re-authenticate.
+pub const REFRESH_TOKEN_WARNING: TimeDelta = TimeDelta::hours(24);
+
 impl Debug for Credentials {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         fn censor(s: &str) -> String {
@@ -51,6 +204,7 @@ impl Debug for Credentials {
             .field("refresh_token", &censor(&self.refresh_token))
             .field("expires_in", &self.expires_in)
             .field("minted", &self.minted)
+            .field("refresh_minted", &self.refresh_minted)
             .finish()
     }
 }
@@ -65,29 +219,109 @@ pub enum CredentialsError {
     SerDe(#[from] serde_json::Error),
     #[error("error in oauth flow: {0}")]
     Oauth2(#[from] rocket_oauth2::Error),
+    #[error("could not decrypt stored credentials")]
+    Cipher,
+    #[error("refresh token expired; log in again")]
+    RefreshTokenExpired,
+    #[error("unusable token response: {0}")]
+    InvalidTokenResponse(&'static str),
+    /// Allows concurrent refreshes of one token to share the failure
+    #[error("{0}")]
+    Shared(Arc<CredentialsError>),
+}
+
+lazy_static! {
+    static ref REFRESHES: RefreshCoordinator = RefreshCoordinator::default();
 }
 
-impl From<TokenResponse<Schwab>> for Credentials {
-    fn from(value: TokenResponse<Schwab>) -> Self {
-        Self::from(&value)
+type RefreshResult = Result<Credentials, Arc<CredentialsError>>;
+
+type SharedRefresh = (Instant, Arc<OnceCell<RefreshResult>>);
+
+/// Makes token refreshes single-flight.
+///
+/// Schwab rotates the refresh token on every refresh, so two requests racing to
+/// refresh the same credentials would invalidate each other. Instead, the first
+/// caller for a refresh token performs the refresh and everyone else awaits its
+/// result. Successful results are kept for [`RefreshCoordinator::GRACE`] so that
+/// callers still holding the old token shortly afterwards get the new one too.
+#[derive(Default)]
+pub struct RefreshCoordinator {
+    refreshes: Mutex<HashMap<String, SharedRefresh>>,
+}
+
+impl RefreshCoordinator {
+    const GRACE: Duration = Duration::from_secs(30);
+
+    pub async fn refresh(&self, oauth2: &OAuth2<Schwab>, refresh_token: &str) -> RefreshResult {
+        let cell = {
+            let mut refreshes = self.refreshes.lock().await;
+
+            refreshes.retain(|_, (started, cell)| {
+                !cell.initialized() || started.elapsed() < Self::GRACE
+            });
+
+            refreshes
+                .entry(refresh_token.to_owned())
+                .or_insert_with(|| (Instant::now(), Arc::default()))
+                .1
+                .clone()
+        };
+
+        let result = cell
+            .get_or_init(|| async {
+                oauth2
+                    .refresh(refresh_token)
+                    .await
+                    .map_err(CredentialsError::Oauth2)
+                    .and_then(Credentials::try_from)
+                    .map_err(Arc::new)
+            })
+            .await
+            .clone();
+
+        if result.is_err() {
+            // don't hold on to failures; the next caller should get to retry
+            let mut refreshes = self.refreshes.lock().await;
+            if refreshes
+                .get(refresh_token)
+                .is_some_and(|(_, c)| Arc::ptr_eq(c, &cell))
+            {
+                refreshes.remove(refresh_token);
+            }
+        }
+
+        result
     }
 }
 
-impl From<&TokenResponse<Schwab>> for Credentials {
-    fn from(value: &TokenResponse<Schwab>) -> Self {
+impl TryFrom<TokenResponse<Schwab>> for Credentials {
+    type Error = CredentialsError;
+
+    fn try_from(value: TokenResponse<Schwab>) -> Result<Self, Self::Error> {
+        Self::try_from(&value)
+    }
+}
+
+impl TryFrom<&TokenResponse<Schwab>> for Credentials {
+    type Error = CredentialsError;
+
+    fn try_from(value: &TokenResponse<Schwab>) -> Result<Self, Self::Error> {
         let now = Utc::now();
 
         let access_token = value.access_token().to_owned();
         let refresh_token = value
             .refresh_token()
-            .expect("TokenResponse should have refresh_token")
+            .ok_or(CredentialsError::InvalidTokenResponse("no refresh_token"))?
             .to_owned();
         let expires_in = value
             .expires_in()
-            .expect("TokenResponse should have expires_in")
-            .to_owned();
+            .filter(|expires_in| *expires_in > 0)
+            .ok_or(CredentialsError::InvalidTokenResponse(
+                "expires_in missing or not positive",
+            ))?;
 
-        Self::new(access_token, refresh_token, expires_in, now)
+        Ok(Self::new(access_token, refresh_token, expires_in, now))
     }
 }
 
@@ -119,11 +353,15 @@ impl Credentials {
             refresh_token,
             expires_in,
             minted,
+            refresh_minted: minted,
         }
     }
 
+    /// The access token, unless it has actually expired. Refreshing early is up to
+    /// callers checking [`Credentials::is_expired`], so tokens issued with less
+    /// than its margin left are still usable.
     pub fn access_token(&self) -> Option<&str> {
-        if self.is_expired() {
+        if self.is_expired_skewed(TimeDelta::zero()) {
             None
         } else {
             Some(&self.access_token)
@@ -134,35 +372,34 @@ impl Credentials {
         &mut self,
         oauth2: &OAuth2<Schwab>,
     ) -> Result<(), CredentialsError> {
-        let token_response = oauth2.refresh(&self.refresh_token).await?;
-        let as_credentials = Credentials::from(token_response);
+        if self.is_refresh_token_expired() {
+            return Err(CredentialsError::RefreshTokenExpired);
+        }
+
+        let as_credentials = REFRESHES
+            .refresh(oauth2, &self.refresh_token)
+            .await
+            .map_err(CredentialsError::Shared)?;
 
-        self.minted = Utc::now();
-        self.access_token = as_credentials
+        // a refresh shared from a moment ago expires with it, not a moment later
+        let access_token = as_credentials
             .access_token()
-            .expect("fresh credentials should not be expired")
+            .ok_or(CredentialsError::InvalidTokenResponse(
+                "access token already expired",
+            ))?
             .to_owned();
+
+        self.minted = as_credentials.minted;
+        self.access_token = access_token;
+        if as_credentials.refresh_token != self.refresh_token {
+            self.refresh_minted = as_credentials.refresh_minted;
+        }
         self.refresh_token = as_credentials.refresh_token().to_owned();
         self.expires_in = as_credentials.expires_in;
 
         Ok(())
     }
 
-    pub async fn ensure_access_token(
-        &mut self,
-        oauth2: &OAuth2<Schwab>,
-    ) -> Result<String, CredentialsError> {
-        if let Some(access_token) = self.access_token() {
-            Ok(access_token.to_owned())
-        } else {
-            self.refresh_access_token(oauth2).await?;
-            Ok(self
-                .access_token()
-                .expect("fresh credentials should not be expired")
-                .to_owned())
-        }
-    }
-
     pub fn refresh_token(&self) -> &str {
         &self.refresh_token
     }
@@ -180,6 +417,21 @@ impl Credentials {
         self.is_expired_skewed(TimeDelta::minutes(5))
     }
 
+    pub fn refresh_token_expires_in(&self) -> TimeDelta {
+        let expiry = self.refresh_minted + REFRESH_TOKEN_LIFETIME;
+        expiry - Utc::now()
+    }
+
+    pub fn is_refresh_token_expired(&self) -> bool {
+        self.refresh_token_expires_in() <= TimeDelta::zero()
+    }
+
+    /// Whether the user should be prompted to log in again before the refresh token runs out.
+    pub fn is_refresh_token_expiring(&self) -> bool {
+        self.refresh_token_expires_in() <= REFRESH_TOKEN_WARNING
+    }
+
+    #[cfg(feature = "debug-routes")]
     pub fn encode(credentials: &Self) -> String {
         let obj = serde_json::to_string(credentials).expect("serializing should never fail");
         BASE64_URL_SAFE_NO_PAD.encode(&obj)
@@ -193,28 +445,243 @@ impl Credentials {
     }
 }
 
-#[get("/login/schwab")]
-pub fn schwab_login(oauth2: OAuth2<Schwab>, cookies: &CookieJar<'_>) -> Redirect {
-    oauth2.get_redirect(cookies, &["api"]).unwrap()
+/// Where users may be sent after logging in, read from the `login` config table.
+///
+/// `next` is accepted if it starts with one of `allowed_redirects`, either a path
+/// on this origin (e.g. `"/"`) or an absolute URL such as a dev server
+/// (`"https://127.0.0.1:5173/"`).
+#[derive(Debug, Deserialize)]
+pub struct LoginRedirects {
+    #[serde(default = "default_redirect")]
+    default_redirect: String,
+    #[serde(default = "default_allowed_redirects")]
+    allowed_redirects: Vec<String>,
+}
+
+fn default_redirect() -> String {
+    "/".to_owned()
+}
+
+fn default_allowed_redirects() -> Vec<String> {
+    vec!["/".to_owned()]
+}
+
+impl Default for LoginRedirects {
+    fn default() -> Self {
+        Self {
+            default_redirect: default_redirect(),
+            allowed_redirects: default_allowed_redirects(),
+        }
+    }
 }
 
-#[get("/auth/schwab")]
-pub fn schwab_callback(token: TokenResponse<Schwab>, cookies: &CookieJar<'_>) -> Redirect {
-    let credentials = Credentials::from(&token);
-    let encoded = Credentials::encode(&credentials);
+impl LoginRedirects {
+    pub fn is_allowed(&self, next: &str) -> bool {
+        // protocol-relative URLs and backslashes can escape a path-only prefix
+        if next.starts_with("//") || next.contains('\\') {
+            return false;
+        }
 
-    let cookie = Cookie::build((AUTH_COOKIE_NAME, Credentials::encode(&credentials)))
-        .path("/")
-        .http_only(true)
-        .secure(true)
-        .same_site(SameSite::Lax)
-        .build();
+        self.allowed_redirects.iter().any(|prefix| {
+            let Some(rest) = next.strip_prefix(prefix.as_str()) else {
+                return false;
+            };
 
-    cookies.add_private(cookie);
+            prefix.ends_with('/') || rest.is_empty() || rest.starts_with(['/', '?', '#'])
+        })
+    }
+
+    fn resolve(&self, next: Option<String>) -> String {
+        next.filter(|next| self.is_allowed(next))
+            .unwrap_or_else(|| self.default_redirect.clone())
+    }
+}
+
+#[get("/login/schwab?<next>")]
+pub fn schwab_login(
+    oauth2: OAuth2<Schwab>,
+    cookies: &CookieJar<'_>,
+    redirects: &State<LoginRedirects>,
+    next: Option<String>,
+) -> Result<Redirect, ApplicationError> {
+    if let Some(next) = &next
+        && !redirects.is_allowed(next)
+    {
+        return Err(ApplicationError::InvalidRedirect(next.clone()));
+    }
+
+    let redirect = oauth2
+        .get_redirect(cookies, &["api"])
+        .map_err(|e| ApplicationError::InvalidCredentials(e.into()))?;
+
+    // bound to this login's OAuth state, so it is only followed by the callback for this login
+    let state = cookies
+        .get_pending(OAUTH2_STATE_COOKIE_NAME)
+        .map(|cookie| cookie.value().to_owned());
+
+    match (next, state) {
+        (Some(next), Some(state)) => cookies.add_private(
+            Cookie::build((NEXT_COOKIE_NAME, format!("{state} {next}")))
+                .path("/")
+                .http_only(true)
+                .secure(true)
+                .same_site(SameSite::Lax)
+                .build(),
+        ),
+        // an abandoned login must not pick the destination of this one
+        _ => cookies.remove_private(NEXT_COOKIE_NAME),
+    }
 
-    Redirect::to(format!("/debug/authenticated#{encoded}"))
+    Ok(redirect)
+}
+
+#[get("/auth/schwab?<state>")]
+pub async fn schwab_callback(
+    token: TokenResponse<Schwab>,
+    state: Option<&str>,
+    cookies: &CookieJar<'_>,
+    sessions: &State<SessionStore>,
+    redirects: &State<LoginRedirects>,
+) -> Result<Redirect, ApplicationError> {
+    let credentials =
+        Credentials::try_from(&token).map_err(ApplicationError::InvalidCredentials)?;
+
+    let session = sessions.create(&credentials).await?;
+    cookies.add_private(session.to_cookie());
+
+    let next = cookies.get_private(NEXT_COOKIE_NAME).and_then(|cookie| {
+        let (bound_to, next) = cookie.value().split_once(' ')?;
+        (Some(bound_to) == state).then(|| next.to_owned())
+    });
+    cookies.remove_private(NEXT_COOKIE_NAME);
+
+    Ok(Redirect::to(redirects.resolve(next)))
+}
+
+/// Token lifetimes for the current session, so clients can prompt for re-login in time.
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct SessionStatus {
+    /// seconds until the access token expires
+    access_token_expires_in: i64,
+    /// seconds until the refresh token expires and the user must log in again
+    refresh_token_expires_in: i64,
+    #[serde(with = "chrono::serde::ts_seconds")]
+    #[schemars(with = "i64")]
+    refresh_token_expires_at: DateTime<Utc>,
+    reauthentication_required_soon: bool,
+}
+
+impl From<&Credentials> for SessionStatus {
+    fn from(value: &Credentials) -> Self {
+        Self {
+            access_token_expires_in: value.expires_in().num_seconds(),
+            refresh_token_expires_in: value.refresh_token_expires_in().num_seconds(),
+            refresh_token_expires_at: value.refresh_minted + REFRESH_TOKEN_LIFETIME,
+            reauthentication_required_soon: value.is_refresh_token_expiring(),
+        }
+    }
+}
+
+#[get("/session")]
+pub fn session_status(user: AuthenticatedUser) -> Json<SessionStatus> {
+    Json(SessionStatus::from(&user.credentials))
+}
+
+const DEFAULT_REVOKE_URI: &str = "https://api.schwabapi.com/v1/oauth/revoke";
+
+/// Revokes tokens at the provider (RFC 7009) when a user logs out.
+///
+/// Reads `client_id`, `client_secret` and an optional `revoke_uri` from the
+/// same `oauth.schwab` config table that `rocket_oauth2` uses.
+#[derive(Debug, Deserialize)]
+pub struct TokenRevoker {
+    client_id: String,
+    client_secret: String,
+    #[serde(default = "default_revoke_uri")]
+    revoke_uri: String,
+    #[serde(skip)]
+    client: Client,
+}
+
+fn default_revoke_uri() -> String {
+    DEFAULT_REVOKE_URI.to_owned()
+}
+
+impl TokenRevoker {
+    pub async fn revoke(&self, credentials: &Credentials) -> Result<(), ApplicationError> {
+        for (token, hint) in [
+            (credentials.refresh_token(), "refresh_token"),
+            (credentials.access_token.as_str(), "access_token"),
+        ] {
+            self.client
+                .post(&self.revoke_uri)
+                .basic_auth(&self.client_id, Some(&self.client_secret))
+                .form(&[("token", token), ("token_type_hint", hint)])
+                .send()
+                .await
+                .and_then(reqwest::Response::error_for_status)
+                .map_err(ApplicationError::Network)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[post("/logout")]
+pub async fn logout(
+    cookies: &CookieJar<'_>,
+    sessions: &State<SessionStore>,
+    revoker: &State<TokenRevoker>,
+    qm: &State<QuotesState>,
+) -> Result<Value, ApplicationError> {
+    let session = SessionId::from_cookies(cookies);
+    cookies.remove_private(SESSION_COOKIE_NAME);
+
+    if let Some(session) = session {
+        qm.end_session(&session).await;
+
+        if let Ok(Some(credentials)) = sessions.load(&session).await
+            && let Err(e) = revoker.revoke(&credentials).await
+        {
+            // the session is gone either way; the tokens will lapse on their own
+            warn!("could not revoke tokens on logout: {e}");
+        }
+
+        sessions.delete(&session).await?;
+    }
+
+    Ok(serde_json::json!({
+            "success": true,
+    }))
 }
 
 pub fn fairing() -> impl Fairing {
-    OAuth2::<Schwab>::fairing("schwab")
+    AdHoc::try_on_ignite("Schwab OAuth", |rocket| async {
+        let revoker = match rocket
+            .figment()
+            .extract_inner::<TokenRevoker>("oauth.schwab")
+        {
+            Ok(revoker) => revoker,
+            Err(e) => {
+                error!("invalid `oauth.schwab` configuration: {e}");
+                return Err(rocket);
+            }
+        };
+
+        let redirects = match rocket.figment().find_value("login") {
+            Ok(_) => match rocket.figment().extract_inner::<LoginRedirects>("login") {
+                Ok(redirects) => redirects,
+                Err(e) => {
+                    error!("invalid `login` configuration: {e}");
+                    return Err(rocket);
+                }
+            },
+            Err(_) => LoginRedirects::default(),
+        };
+
+        Ok(rocket
+            .manage(revoker)
+            .manage(redirects)
+            .attach(OAuth2::<Schwab>::fairing("schwab")))
+    })
 }
@@ -1,12 +1,22 @@
 use chrono::Utc;
 use rocket_dyn_templates::{Template, context};
 
+use crate::{oauth::Credentials, session::AuthenticatedUser};
+
 #[get("/authenticated")]
-pub fn authenticated_page() -> Template {
+pub fn authenticated_page(user: Option<AuthenticatedUser>) -> Template {
+    // raw tokens never leave the server in release builds
+    let credentials = if cfg!(debug_assertions) {
+        user.map(|user| Credentials::encode(&user.credentials))
+    } else {
+        None
+    };
+
     Template::render(
         "authenticated",
         context! {
             timestamp: Utc::now().to_string(),
+            credentials,
         },
     )
 }
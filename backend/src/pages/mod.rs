@@ -2,6 +2,38 @@ mod authenticated;
 mod index;
 mod login;
 
+use rocket::fairing::{AdHoc, Fairing};
+use rocket_dyn_templates::Template;
+
 pub use authenticated::authenticated_page;
 pub use index::index_page;
 pub use login::login_page;
+
+/// Mounts the `/debug` pages and their templates.
+///
+/// Enabled by the `debug_routes` config flag, which defaults to on in debug
+/// builds and off in release builds.
+pub fn fairing() -> impl Fairing {
+    AdHoc::on_ignite("Debug routes", |rocket| async {
+        let enabled = rocket
+            .figment()
+            .extract_inner::<bool>("debug_routes")
+            .unwrap_or(cfg!(debug_assertions));
+
+        if !enabled {
+            return rocket;
+        }
+
+        rocket
+            .mount(
+                "/debug",
+                routes![
+                    login_page,
+                    index_page,
+                    authenticated_page,
+                    crate::schwab::endpoints::refresh_token_debug
+                ],
+            )
+            .attach(Template::fairing())
+    })
+}
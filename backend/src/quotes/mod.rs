@@ -1,66 +1,215 @@
 mod poller;
+pub mod protocol;
 
-use std::{collections::HashMap, sync::Arc, time::Duration};
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 pub use poller::Poller;
-use rocket::tokio::sync::RwLock;
+use rocket::tokio::sync::{RwLock, watch};
+use serde::Deserialize;
 
 use crate::{
+    broker::{BrokerQuote, BrokerQuotes, DynBroker},
     errors::ApplicationError,
     oauth::Credentials,
-    quotes::poller::Subscription,
-    schwab::{self, schema::QuoteResponse},
+    quotes::poller::{Select, Subscription},
+    session::SessionId,
+    symbol::Symbol,
 };
 
+const POLL_DELAY: Duration = Duration::from_millis(500);
+
+type QuotePoller = Poller<Result<BrokerQuotes, Arc<ApplicationError>>, Symbol>;
+
+pub type QuoteSubscription = Subscription<Result<BrokerQuotes, Arc<ApplicationError>>, Symbol>;
+
+impl Select<Symbol> for Result<BrokerQuotes, Arc<ApplicationError>> {
+    fn select(&self, states: &[Symbol]) -> Self {
+        let quotes = self.as_ref().map_err(Arc::clone)?;
+
+        Ok(quotes.select(states))
+    }
+}
+
+/// How quotes are cached and polled, read from the `quotes` config table.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct QuotesConfig {
+    /// how old a cached quote may be before it is fetched again
+    pub max_age_ms: u64,
+    /// how long a symbol nobody asks for stays cached
+    pub retain_ms: u64,
+    /// how many polls a stream client may fall behind before it skips ahead and resyncs
+    pub queue_depth: usize,
+}
+
+impl Default for QuotesConfig {
+    fn default() -> Self {
+        Self {
+            max_age_ms: POLL_DELAY.as_millis() as u64,
+            retain_ms: 5 * 60 * 1000,
+            queue_depth: 16,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct CachedQuote {
+    fetched: Instant,
+    quote: BrokerQuote,
+}
+
+/// The latest quote fetched for each symbol, by any session.
+///
+/// Market data is not account-specific, so if two users are watching the same
+/// symbol only one of them needs to spend a request on it, and a one-off
+/// request for a symbol someone is streaming can be answered right away.
 #[derive(Debug)]
-pub struct QuotesState {
-    poller: Poller<Result<QuoteResponse, Arc<ApplicationError>>, String>,
-    credentials: Arc<RwLock<Option<Credentials>>>,
+struct LatestQuotes {
+    config: QuotesConfig,
+    quotes: RwLock<HashMap<String, CachedQuote>>,
 }
 
-impl QuotesState {
-    pub fn new() -> Self {
-        let credentials: Arc<RwLock<Option<Credentials>>> = Arc::default();
-        let c2 = credentials.clone();
-
-        let poller = Poller::new(16, Duration::from_millis(500), move |client, states| {
-            let client = client.clone();
-            let credentials = credentials.clone();
-            Box::pin(async move {
-                let credentials = credentials.read().await;
-                let Some(credentials) = credentials.clone() else {
-                    panic!("credentials were not set");
-                };
-
-                if states.is_empty() {
-                    return Ok(QuoteResponse {
-                        quotes: HashMap::new(),
-                    });
+impl LatestQuotes {
+    fn new(config: QuotesConfig) -> Self {
+        Self {
+            config,
+            quotes: RwLock::default(),
+        }
+    }
+
+    /// Split `symbols` into quotes that are still fresh and symbols that need fetching.
+    async fn partition(&self, symbols: Vec<Symbol>) -> (HashMap<String, BrokerQuote>, Vec<Symbol>) {
+        let max_age = Duration::from_millis(self.config.max_age_ms);
+        let quotes = self.quotes.read().await;
+
+        let mut fresh = HashMap::new();
+        let mut missing = vec![];
+
+        for symbol in symbols {
+            match quotes.get(symbol.as_str()) {
+                Some(cached) if cached.fetched.elapsed() < max_age => {
+                    fresh.insert(symbol.into(), cached.quote.clone());
                 }
+                _ => missing.push(symbol),
+            }
+        }
 
-                let response = schwab::get_quote(credentials, Some(client), states).await?;
+        (fresh, missing)
+    }
 
-                Ok(response)
-            })
-        });
+    /// Cache the quotes in `response`, dropping symbols not fetched for `retain_ms`.
+    async fn record(&self, response: &BrokerQuotes) {
+        let now = Instant::now();
+        let retain = Duration::from_millis(self.config.retain_ms);
+        let mut quotes = self.quotes.write().await;
 
-        Self {
-            poller,
-            credentials: c2,
+        quotes.retain(|_, cached| now.duration_since(cached.fetched) < retain);
+
+        for (symbol, quote) in &response.quotes {
+            quotes.insert(
+                symbol.clone(),
+                CachedQuote {
+                    fetched: now,
+                    quote: quote.clone(),
+                },
+            );
         }
     }
+}
+
+/// Per-session quote polling: every session polls with its own credentials
+/// and its own symbol set.
+#[derive(Debug)]
+pub struct SessionQuotes {
+    poller: QuotePoller,
+    broker: DynBroker,
+    latest: Arc<LatestQuotes>,
+    credentials: Arc<RwLock<Option<Credentials>>>,
+    ended: watch::Sender<bool>,
+}
 
-    pub async fn set_quotes(&self, quotes: Vec<String>) {
-        self.poller.set_state(quotes).await
+/// Quotes for `symbols`, from `latest` where fresh and from `broker` otherwise.
+async fn fetch(
+    broker: &DynBroker,
+    latest: &LatestQuotes,
+    credentials: &RwLock<Option<Credentials>>,
+    symbols: Vec<Symbol>,
+) -> Result<BrokerQuotes, ApplicationError> {
+    if symbols.is_empty() {
+        return Ok(BrokerQuotes::default());
     }
 
-    pub async fn extend_quotes(&self, quotes: Vec<String>) {
-        self.poller.extend_unique(quotes).await
+    let (quotes, missing) = latest.partition(symbols).await;
+    let mut quotes = BrokerQuotes {
+        quotes,
+        errors: None,
+    };
+
+    if !missing.is_empty() {
+        let credentials = credentials.read().await;
+        let Some(credentials) = credentials.as_ref() else {
+            return Err(ApplicationError::MissingAuthentication);
+        };
+
+        let response = broker.quotes(credentials, missing).await?;
+        latest.record(&response).await;
+        quotes.quotes.extend(response.quotes);
+        quotes.errors = response.errors;
     }
 
-    pub async fn subscribe(
-        &self,
-    ) -> Subscription<Result<QuoteResponse, Arc<ApplicationError>>, String> {
+    Ok(quotes)
+}
+
+impl SessionQuotes {
+    fn new(broker: DynBroker, latest: Arc<LatestQuotes>) -> Self {
+        let credentials: Arc<RwLock<Option<Credentials>>> = Arc::default();
+
+        let poller = Poller::new(
+            latest.config.queue_depth.max(1),
+            POLL_DELAY,
+            {
+                let broker = broker.clone();
+                move || broker.quote_pace()
+            },
+            {
+                let broker = broker.clone();
+                let latest = latest.clone();
+                let credentials = credentials.clone();
+                move |states| {
+                    let broker = broker.clone();
+                    let latest = latest.clone();
+                    let credentials = credentials.clone();
+                    Box::pin(async move {
+                        fetch(&broker, &latest, &credentials, states)
+                            .await
+                            .map_err(Arc::new)
+                    })
+                }
+            },
+        );
+
+        Self {
+            poller,
+            broker,
+            latest,
+            credentials,
+            ended: watch::Sender::new(false),
+        }
+    }
+
+    /// Quotes for `symbols` right away: cached ones as they are, the rest fetched
+    /// now rather than on the poller's next tick.
+    pub async fn fetch(&self, symbols: Vec<Symbol>) -> Result<BrokerQuotes, ApplicationError> {
+        fetch(&self.broker, &self.latest, &self.credentials, symbols).await
+    }
+
+    /// Subscribe to this session's quotes. The subscription starts out watching
+    /// no symbols; the poller polls the union of every subscription's symbols.
+    pub async fn subscribe(&self) -> QuoteSubscription {
         self.poller.subscribe()
     }
 
@@ -69,4 +218,53 @@ impl QuotesState {
 
         *w = Some(credentials);
     }
+
+    /// Resolves (changes to `true`) when the session is logged out.
+    pub fn ended(&self) -> watch::Receiver<bool> {
+        self.ended.subscribe()
+    }
+}
+
+#[derive(Debug)]
+pub struct QuotesState {
+    broker: DynBroker,
+    sessions: RwLock<HashMap<SessionId, Arc<SessionQuotes>>>,
+    latest: Arc<LatestQuotes>,
+}
+
+impl QuotesState {
+    pub fn new(broker: DynBroker, config: QuotesConfig) -> Self {
+        Self {
+            broker,
+            sessions: RwLock::default(),
+            latest: Arc::new(LatestQuotes::new(config)),
+        }
+    }
+
+    /// Get the quote state for `session`, creating it if this is the session's first request.
+    ///
+    /// Sessions without any live subscribers are pruned along the way.
+    pub async fn session(&self, session: &SessionId) -> Arc<SessionQuotes> {
+        if let Some(quotes) = self.sessions.read().await.get(session) {
+            return quotes.clone();
+        }
+
+        let mut sessions = self.sessions.write().await;
+
+        sessions.retain(|id, quotes| id == session || quotes.poller.subscriber_count() > 0);
+
+        sessions
+            .entry(session.clone())
+            .or_insert_with(|| {
+                Arc::new(SessionQuotes::new(self.broker.clone(), self.latest.clone()))
+            })
+            .clone()
+    }
+
+    /// Forget `session` and tell its open streams to close.
+    pub async fn end_session(&self, session: &SessionId) {
+        if let Some(quotes) = self.sessions.write().await.remove(session) {
+            quotes.ended.send_replace(true);
+        }
+    }
 }
@@ -1,4 +1,5 @@
 use std::{
+    collections::HashMap,
     fmt::Debug,
     future::Future,
     pin::Pin,
@@ -9,21 +10,84 @@ use std::{
     time::Duration,
 };
 
-use reqwest::Client;
 use rocket::tokio::{
     spawn,
-    sync::{Mutex, RwLock, broadcast},
+    sync::{
+        Mutex, RwLock,
+        broadcast::{self, error::RecvError},
+    },
     task::JoinHandle,
     time::sleep,
 };
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    impl Select<u32> for u32 {
+        fn select(&self, _states: &[u32]) -> Self {
+            *self
+        }
+    }
+
+    #[rocket::async_test]
+    async fn lagged_subscribers_skip_ahead() {
+        use rocket::tokio::sync::{Semaphore, watch};
+
+        // each poll waits for a permit, and reports how many polls have started
+        let permits = Arc::new(Semaphore::new(0));
+        let (started, mut polls_started) = watch::channel(0);
+
+        let mut polls: u32 = 0;
+        let poller = Poller::new(2, Duration::ZERO, || Duration::ZERO, {
+            let permits = permits.clone();
+            move |_states: Vec<u32>| {
+                polls += 1;
+                let value = polls;
+                started.send_replace(value);
+
+                let permits = permits.clone();
+                Box::pin(async move {
+                    permits.acquire().await.unwrap().forget();
+                    value
+                }) as BoxFuture<u32>
+            }
+        });
+
+        let mut subscription = poller.subscribe();
+        subscription.set_state(vec![1]).await;
+        assert_eq!(subscription.states().await, [1]);
+
+        // once the sixth poll starts, the first five have been broadcast
+        permits.add_permits(5);
+        polls_started.wait_for(|&polls| polls == 6).await.unwrap();
+
+        let Err(RecvError::Lagged(skipped)) = subscription.recv().await else {
+            panic!("a subscriber that never read should lag");
+        };
+        assert_eq!(skipped, 3);
+
+        // the next values are fresh polls, not the buffered 4 and 5
+        permits.add_permits(2);
+        assert_eq!(subscription.recv().await.unwrap(), 6);
+        assert_eq!(subscription.recv().await.unwrap(), 7);
+    }
+}
+
 type BoxFuture<T> = Pin<Box<dyn Future<Output = T> + Send + 'static>>;
 
-type Callback<T, State> = dyn FnMut(&Client, Vec<State>) -> BoxFuture<T> + Send + 'static;
+type Callback<T, State> = dyn FnMut(Vec<State>) -> BoxFuture<T> + Send + 'static;
+
+type Pace = dyn Fn() -> Duration + Send + Sync + 'static;
 
-pub trait StateLike: Send + Sync + PartialEq + 'static {}
+pub trait StateLike: Send + Sync + PartialEq + Clone + 'static {}
 
-impl<T> StateLike for T where T: Send + Sync + PartialEq + 'static {}
+impl<T> StateLike for T where T: Send + Sync + PartialEq + Clone + 'static {}
+
+/// A polled value that can be narrowed down to the part a single subscriber asked for.
+pub trait Select<State> {
+    fn select(&self, states: &[State]) -> Self;
+}
 
 struct Inner<T, State>
 where
@@ -36,15 +100,38 @@ where
     // lifecycle
     handle: Mutex<Option<JoinHandle<()>>>,
     subscribers: AtomicUsize,
+    next_id: AtomicUsize,
 
-    // shared state
-    queued: RwLock<Vec<State>>,
+    // shared state: what each subscriber is interested in, keyed by subscriber id
+    interests: RwLock<HashMap<usize, Vec<State>>>,
     delay: Duration,
+    // extra delay asked for on top of `delay`, e.g. by a rate limiter
+    pace: Box<Pace>,
 
     // FnMut needs interior mutability
     cb: Mutex<Box<Callback<T, State>>>,
 }
 
+impl<T, State> Inner<T, State>
+where
+    T: Clone + Send + 'static,
+    State: StateLike,
+{
+    /// Every state at least one subscriber is interested in, without duplicates.
+    async fn union(&self) -> Vec<State> {
+        let interests = self.interests.read().await;
+
+        let mut result = vec![];
+        for state in interests.values().flatten() {
+            if !result.contains(state) {
+                result.push(state.clone());
+            }
+        }
+
+        result
+    }
+}
+
 #[derive(Clone)]
 pub struct Poller<T, State>
 where
@@ -62,7 +149,7 @@ where
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Poller")
             .field("delay", &self.inner.delay)
-            .field("state", &self.inner.queued.try_read())
+            .field("interests", &self.inner.interests.try_read())
             .finish()
     }
 }
@@ -72,17 +159,71 @@ where
     T: Clone + Send + 'static,
     State: StateLike,
 {
+    id: usize,
     rx: broadcast::Receiver<T>,
     inner: Arc<Inner<T, State>>,
 }
 
+impl<T, State> Subscription<T, State>
+where
+    T: Clone + Send + Select<State> + 'static,
+    State: StateLike,
+{
+    /// Receive the next polled value, narrowed to this subscriber's states.
+    ///
+    /// A subscriber that fell more than the poller's buffer behind gets
+    /// [`RecvError::Lagged`] with the number of values it missed, and skips
+    /// ahead to the next value polled rather than catching up on old ones.
+    pub async fn recv(&mut self) -> Result<T, RecvError> {
+        let value = match self.rx.recv().await {
+            Ok(value) => value,
+            Err(RecvError::Lagged(skipped)) => {
+                self.rx = self.rx.resubscribe();
+                return Err(RecvError::Lagged(skipped));
+            }
+            Err(e) => return Err(e),
+        };
+        let interests = self.inner.interests.read().await;
+        let states = interests.get(&self.id).map(Vec::as_slice).unwrap_or(&[]);
+
+        Ok(value.select(states))
+    }
+}
+
 impl<T, State> Subscription<T, State>
 where
     T: Clone + Send + 'static,
     State: StateLike,
 {
-    pub async fn recv(&mut self) -> Result<T, broadcast::error::RecvError> {
-        self.rx.recv().await
+    /// This subscriber's states.
+    pub async fn states(&self) -> Vec<State> {
+        let interests = self.inner.interests.read().await;
+        interests.get(&self.id).cloned().unwrap_or_default()
+    }
+
+    /// Replace this subscriber's states.
+    pub async fn set_state(&self, states: Vec<State>) {
+        let mut interests = self.inner.interests.write().await;
+        interests.insert(self.id, states);
+    }
+
+    pub async fn extend_unique(&self, states: Vec<State>) {
+        let mut interests = self.inner.interests.write().await;
+        let q = interests.entry(self.id).or_default();
+
+        for state in states {
+            if !q.contains(&state) {
+                q.push(state);
+            }
+        }
+    }
+
+    pub async fn remove(&self, states: &[State]) {
+        let mut interests = self.inner.interests.write().await;
+
+        if let Some(q) = interests.get_mut(&self.id) {
+            q.retain(|state| !states.contains(state));
+        }
     }
 }
 
@@ -92,22 +233,27 @@ where
     State: StateLike,
 {
     fn drop(&mut self) {
+        let id = self.id;
+        let inner = self.inner.clone();
+
         // If this was the last subscriber, stop the poll loop.
         let prev = self.inner.subscribers.fetch_sub(1, Ordering::AcqRel);
-        if prev == 1 {
-            // last one out turns off the lights
-            // abort asynchronously-safe: just abort the JoinHandle if present
-            let inner = self.inner.clone();
-            // We can’t .await in Drop; spawn a tiny task to do it.
-            spawn(async move {
+
+        // We can’t .await in Drop; spawn a tiny task to do it.
+        spawn(async move {
+            if prev == 1 {
+                // last one out turns off the lights
+                // abort asynchronously-safe: just abort the JoinHandle if present
                 let mut h = inner.handle.lock().await;
                 if let Some(handle) = h.take() {
                     handle.abort();
                 }
-                let mut q = inner.queued.write().await;
-                q.clear();
-            });
-        }
+            }
+
+            // states nobody else asked for fall out of the next poll's union
+            let mut q = inner.interests.write().await;
+            q.remove(&id);
+        });
     }
 }
 
@@ -132,28 +278,23 @@ where
 
             let task_inner = inner.clone();
             let handle = spawn(async move {
-                let client = Client::new();
-
                 loop {
                     // If nobody is listening, stop.
                     if task_inner.subscribers.load(Ordering::Acquire) == 0 {
                         break;
                     }
 
-                    let owned = {
-                        let mut q = task_inner.queued.write().await;
-                        std::mem::take(&mut *q)
-                    };
+                    let owned = task_inner.union().await;
 
                     let value = {
                         let mut cb = task_inner.cb.lock().await;
-                        (cb)(&client, owned).await
+                        (cb)(owned).await
                     };
 
                     // broadcast::Sender::send is synchronous; ignore "no receivers" errors
                     let _ = task_inner.tx.send(value);
 
-                    sleep(task_inner.delay).await;
+                    sleep(task_inner.delay + (task_inner.pace)()).await;
                 }
             });
 
@@ -161,9 +302,13 @@ where
         });
     }
 
-    pub fn new<F>(buffer: usize, delay: Duration, cb: F) -> Self
+    /// Poll every `delay`, plus however long `pace()` asks for at the time.
+    ///
+    /// Each subscriber may fall up to `buffer` values behind before it lags.
+    pub fn new<P, F>(buffer: usize, delay: Duration, pace: P, cb: F) -> Self
     where
-        F: FnMut(&Client, Vec<State>) -> BoxFuture<T> + Send + 'static,
+        P: Fn() -> Duration + Send + Sync + 'static,
+        F: FnMut(Vec<State>) -> BoxFuture<T> + Send + 'static,
     {
         let (tx, _rx_unused) = broadcast::channel::<T>(buffer);
 
@@ -171,32 +316,24 @@ where
             tx,
             handle: Mutex::new(None),
             subscribers: AtomicUsize::new(0),
-            queued: RwLock::new(Vec::new()),
+            next_id: AtomicUsize::new(0),
+            interests: RwLock::new(HashMap::new()),
             delay,
+            pace: Box::new(pace),
             cb: Mutex::new(Box::new(cb)),
         });
 
         Self { inner }
     }
 
-    pub async fn set_state(&self, states: Vec<State>) {
-        let mut q = self.inner.queued.write().await;
-        *q = states;
+    pub fn subscriber_count(&self) -> usize {
+        self.inner.subscribers.load(Ordering::Acquire)
     }
 
-    pub async fn extend_unique(&self, states: Vec<State>) {
-        let mut q = self.inner.queued.write().await;
-
-        for state in states {
-            if !q.contains(&state) {
-                q.push(state);
-            }
-        }
-    }
-
-    /// Create a subscriber. If this is the first subscriber, the poller spawns.
+    /// Create a subscriber with no states. If this is the first subscriber, the poller spawns.
     pub fn subscribe(&self) -> Subscription<T, State> {
         let prev = self.inner.subscribers.fetch_add(1, Ordering::AcqRel);
+        let id = self.inner.next_id.fetch_add(1, Ordering::Relaxed);
 
         // each subscriber gets its own receiver
         let rx = self.inner.tx.subscribe();
@@ -207,6 +344,7 @@ where
         }
 
         Subscription {
+            id,
             rx,
             inner: self.inner.clone(),
         }
@@ -226,10 +364,10 @@ where
             return;
         };
 
-        if let Some(handle) = x.take() {
-            if !handle.is_finished() {
-                handle.abort();
-            }
+        if let Some(handle) = x.take()
+            && !handle.is_finished()
+        {
+            handle.abort();
         }
     }
 }
@@ -0,0 +1,333 @@
+use schemars::{JsonSchema, Schema, generate::SchemaSettings};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    broker::FormattedQuotes, errors::ApplicationError, oauth::SessionStatus, symbol::Symbol,
+};
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs};
+
+    use serde_json::json;
+
+    use super::*;
+
+    fn nack(text: &str) -> (Option<String>, StreamError) {
+        match ClientMessage::parse(text) {
+            Err(ServerBody::Nack { id, error }) => (id, error),
+            other => panic!("expected a nack for {text}, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn client_messages() {
+        let message =
+            ClientMessage::parse(r#"{"v":1,"id":"7","type":"add","symbols":["aapl"]}"#).unwrap();
+        assert_eq!(message.id.as_deref(), Some("7"));
+        let ClientRequest::Add { symbols } = message.request else {
+            panic!("expected add, got {:?}", message.request);
+        };
+        assert_eq!(symbols[0].as_str(), "AAPL");
+
+        // the version and request id are optional
+        let message = ClientMessage::parse(r#"{"type":"ping"}"#).unwrap();
+        assert_eq!((message.v, message.id), (PROTOCOL_VERSION, None));
+
+        let (id, error) = nack(r#"{"id":"8","type":"subscribe","symbols":["NOT A SYMBOL!"]}"#);
+        assert_eq!(id.as_deref(), Some("8"));
+        assert_eq!(error.code, ErrorCode::InvalidMessage);
+
+        let (id, error) = nack(r#"{"v":99,"id":"9","type":"ping"}"#);
+        assert_eq!(id.as_deref(), Some("9"));
+        assert_eq!(error.code, ErrorCode::UnsupportedVersion);
+
+        // a newer client's new message types are a version problem, not a malformed message
+        let (id, error) = nack(r#"{"v":2,"id":"10","type":"unsubscribe_all"}"#);
+        assert_eq!(id.as_deref(), Some("10"));
+        assert_eq!(error.code, ErrorCode::UnsupportedVersion);
+
+        assert_eq!(nack("not json").0, None);
+    }
+
+    #[test]
+    fn server_messages_are_enveloped() {
+        let ack = ServerMessage::from(ServerBody::Ack {
+            id: Some("7".to_owned()),
+        });
+        assert_eq!(
+            serde_json::to_value(&ack).unwrap(),
+            json!({ "v": PROTOCOL_VERSION, "type": "ack", "id": "7" })
+        );
+
+        let status = ServerMessage::from(ServerBody::Status(StreamStatus::UpstreamUnavailable {
+            retry_after_ms: 250,
+        }));
+        assert_eq!(
+            serde_json::to_value(&status).unwrap(),
+            json!({
+                "v": PROTOCOL_VERSION, "type": "status",
+                "status": "upstream_unavailable", "retry_after_ms": 250
+            })
+        );
+
+        let error = ApplicationError::Polling(
+            ApplicationError::Retried {
+                attempts: 3,
+                error: Box::new(ApplicationError::UpstreamRateLimited),
+            }
+            .into(),
+        );
+        let error = ServerMessage::from(ServerBody::Error(StreamError::from(&error)));
+        let error = serde_json::to_value(&error).unwrap();
+        assert_eq!(error["type"], "error");
+        assert_eq!(error["code"], "rate_limited");
+        assert!(error["message"].as_str().unwrap().contains("3 attempts"));
+    }
+
+    /// Fails when the protocol types change without the checked in schemas being
+    /// regenerated; run with `UPDATE_SCHEMA=1` to regenerate them.
+    #[test]
+    fn schemas_are_up_to_date() {
+        let schemas = [
+            ("quote-stream-client.schema.json", client_schema()),
+            ("quote-stream-server.schema.json", server_schema()),
+        ];
+
+        for (name, schema) in schemas {
+            let path = format!("{}/schema/{name}", env!("CARGO_MANIFEST_DIR"));
+            let generated = serde_json::to_string_pretty(&schema).unwrap() + "\n";
+
+            if env::var_os("UPDATE_SCHEMA").is_some() {
+                fs::write(&path, &generated).unwrap();
+            }
+
+            let checked_in = fs::read_to_string(&path).unwrap_or_default();
+            assert!(
+                checked_in == generated,
+                "{name} is out of date; run the tests with UPDATE_SCHEMA=1"
+            );
+        }
+    }
+}
+
+/// Version of the quote stream protocol, announced in `hello`.
+///
+/// Bumped for changes that old clients would misread; adding message types or
+/// optional fields doesn't count.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+fn current_version() -> u32 {
+    PROTOCOL_VERSION
+}
+
+/// A message from a stream client, e.g.
+/// `{"v":1,"id":"1","type":"subscribe","symbols":["AAPL"]}`.
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct ClientMessage {
+    /// the protocol version the client speaks; the current one if left out
+    #[serde(default = "current_version")]
+    // checked on the raw message in `parse`, before the rest is read
+    #[allow(dead_code)]
+    pub v: u32,
+    /// echoed in the `ack` or `nack` for this message
+    #[serde(default)]
+    pub id: Option<String>,
+    #[serde(flatten)]
+    pub request: ClientRequest,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientRequest {
+    /// Watch these symbols as well.
+    Add { symbols: Vec<Symbol> },
+    /// Stop watching these symbols.
+    Remove { symbols: Vec<Symbol> },
+    /// Watch exactly these symbols.
+    Subscribe { symbols: Vec<Symbol> },
+    /// Does nothing but get acked, to check the connection.
+    Ping,
+}
+
+impl ClientMessage {
+    /// Parse a text frame, or the `nack` to answer it with.
+    pub fn parse(text: &str) -> Result<Self, ServerBody> {
+        let nack = |id: Option<String>, code, message: String| ServerBody::Nack {
+            id,
+            error: StreamError {
+                code,
+                message,
+                retry_after_ms: None,
+            },
+        };
+
+        let value: Value = serde_json::from_str(text)
+            .map_err(|e| nack(None, ErrorCode::InvalidMessage, e.to_string()))?;
+        let id = value.get("id").and_then(Value::as_str).map(str::to_owned);
+
+        // before the body, which a newer version may well have changed
+        let version = match value.get("v") {
+            Some(v) => u32::deserialize(v)
+                .map_err(|e| nack(id.clone(), ErrorCode::InvalidMessage, e.to_string()))?,
+            None => current_version(),
+        };
+        if version != PROTOCOL_VERSION {
+            return Err(nack(
+                id,
+                ErrorCode::UnsupportedVersion,
+                format!("this server speaks version {PROTOCOL_VERSION}"),
+            ));
+        }
+
+        Self::deserialize(value).map_err(|e| nack(id, ErrorCode::InvalidMessage, e.to_string()))
+    }
+}
+
+/// A message to a stream client: the protocol version plus one [`ServerBody`].
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct ServerMessage {
+    pub v: u32,
+    #[serde(flatten)]
+    pub body: ServerBody,
+}
+
+impl From<ServerBody> for ServerMessage {
+    fn from(body: ServerBody) -> Self {
+        Self {
+            v: PROTOCOL_VERSION,
+            body,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerBody {
+    /// The first message on every connection.
+    Hello {
+        protocol: u32,
+        session: SessionStatus,
+    },
+    /// The client message with this `id` was applied.
+    Ack { id: Option<String> },
+    /// The client message with this `id` was rejected and had no effect.
+    Nack {
+        id: Option<String>,
+        error: StreamError,
+    },
+    /// Quotes for every watched symbol, sent outside the regular updates.
+    Snapshot {
+        /// the last good quotes, kept during an upstream outage
+        stale: bool,
+        /// sent after the client fell too far behind and missed some updates
+        resync: bool,
+        quotes: FormattedQuotes,
+    },
+    /// The latest poll of the watched symbols.
+    Update { quotes: FormattedQuotes },
+    /// Something went wrong outside of any client message.
+    Error(StreamError),
+    /// A change in the connection's circumstances.
+    Status(StreamStatus),
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum StreamStatus {
+    /// The refresh token is about to expire; the user should log in again.
+    ReauthenticationRequired(SessionStatus),
+    /// The broker is down; updates resume by themselves once it recovers.
+    UpstreamUnavailable { retry_after_ms: u64 },
+    /// The broker is back after being unavailable.
+    UpstreamRecovered,
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+pub struct StreamError {
+    pub code: ErrorCode,
+    /// for people; match on `code` instead
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry_after_ms: Option<u64>,
+}
+
+/// What went wrong, as a stable identifier clients can match on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// not JSON, not a known message type or with invalid fields
+    InvalidMessage,
+    UnsupportedVersion,
+    /// the session's credentials no longer work; log in again
+    Unauthorized,
+    RateLimited,
+    UpstreamUnavailable,
+    /// the broker failed or sent something unexpected
+    UpstreamError,
+    Internal,
+}
+
+/// The error underneath any sharing or retry wrappers.
+fn root_cause(error: &ApplicationError) -> &ApplicationError {
+    match error {
+        ApplicationError::Polling(error) => root_cause(error),
+        ApplicationError::Retried { error, .. } => root_cause(error),
+        error => error,
+    }
+}
+
+impl From<&ApplicationError> for ErrorCode {
+    fn from(error: &ApplicationError) -> Self {
+        match root_cause(error) {
+            ApplicationError::InvalidSymbol(_) | ApplicationError::MissingQueryParameters(_) => {
+                Self::InvalidMessage
+            }
+            ApplicationError::MissingAuthentication
+            | ApplicationError::InvalidCredentials(_)
+            | ApplicationError::UpstreamUnauthorized => Self::Unauthorized,
+            ApplicationError::UpstreamRateLimited | ApplicationError::RateLimited { .. } => {
+                Self::RateLimited
+            }
+            ApplicationError::UpstreamUnavailable { .. } => Self::UpstreamUnavailable,
+            ApplicationError::Network(_)
+            | ApplicationError::QuoteResponseDeserialization(_)
+            | ApplicationError::UpstreamBadRequest { .. }
+            | ApplicationError::UpstreamFailed { .. } => Self::UpstreamError,
+            _ => Self::Internal,
+        }
+    }
+}
+
+impl From<&ApplicationError> for StreamError {
+    fn from(error: &ApplicationError) -> Self {
+        let retry_after_ms = match root_cause(error) {
+            ApplicationError::RateLimited { retry_after_ms }
+            | ApplicationError::UpstreamUnavailable { retry_after_ms } => Some(*retry_after_ms),
+            _ => None,
+        };
+
+        Self {
+            code: error.into(),
+            message: error.to_string(),
+            retry_after_ms,
+        }
+    }
+}
+
+/// JSON Schema for the messages clients send, e.g. to generate client types from.
+pub fn client_schema() -> Schema {
+    SchemaSettings::default()
+        .for_deserialize()
+        .into_generator()
+        .into_root_schema_for::<ClientMessage>()
+}
+
+/// JSON Schema for the messages the server sends.
+pub fn server_schema() -> Schema {
+    SchemaSettings::default()
+        .for_serialize()
+        .into_generator()
+        .into_root_schema_for::<ServerMessage>()
+}
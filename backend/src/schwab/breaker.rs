@@ -0,0 +1,341 @@
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::errors::ApplicationError;
+
+#[cfg(test)]
+mod tests {
+    use std::{future::pending, thread::sleep};
+
+    use rocket::tokio::time::timeout;
+
+    use super::*;
+
+    fn breaker() -> CircuitBreaker {
+        CircuitBreaker::new(BreakerConfig {
+            failure_threshold: 2,
+            open_ms: 20,
+            max_open_ms: 50,
+        })
+    }
+
+    fn outage() -> ApplicationError {
+        ApplicationError::Retried {
+            attempts: 3,
+            error: Box::new(ApplicationError::UpstreamFailed {
+                status: 503,
+                detail: "Service Unavailable".to_owned(),
+            }),
+        }
+    }
+
+    #[test]
+    fn opens_after_consecutive_failures() {
+        let breaker = breaker();
+
+        assert!(breaker.check().is_ok());
+        breaker.record(false);
+        breaker.record(true);
+        breaker.record(false);
+        assert_eq!(breaker.state(), BreakerState::Closed);
+
+        breaker.record(false);
+        assert_eq!(breaker.state(), BreakerState::Open);
+        assert!(matches!(
+            breaker.check(),
+            Err(ApplicationError::UpstreamUnavailable { retry_after_ms }) if retry_after_ms <= 20
+        ));
+        assert!(breaker.remaining() > Duration::ZERO);
+    }
+
+    #[test]
+    fn half_open_lets_one_probe_through() {
+        let breaker = breaker();
+        breaker.record(false);
+        breaker.record(false);
+
+        sleep(Duration::from_millis(25));
+        let probe = breaker.check().unwrap();
+        assert_eq!(breaker.state(), BreakerState::HalfOpen);
+        assert!(breaker.check().is_err());
+
+        probe.record_result(&Ok(()));
+        assert_eq!(breaker.state(), BreakerState::Closed);
+        assert!(breaker.check().is_ok());
+    }
+
+    #[rocket::async_test]
+    async fn dropped_probes_are_abandoned() {
+        let breaker = breaker();
+        breaker.record(false);
+        breaker.record(false);
+        sleep(Duration::from_millis(25));
+
+        // a probe whose call is cancelled before Schwab answers, e.g. by an aborted poller
+        let call = async {
+            let _probe = breaker.check().unwrap();
+            pending::<()>().await;
+        };
+        assert!(timeout(Duration::from_millis(5), call).await.is_err());
+
+        assert_eq!(breaker.state(), BreakerState::Open);
+        let probe = breaker.check().unwrap();
+        assert_eq!(breaker.state(), BreakerState::HalfOpen);
+        probe.record_result(&Ok(()));
+        assert_eq!(breaker.state(), BreakerState::Closed);
+    }
+
+    #[test]
+    fn only_outages_count() {
+        let breaker = breaker();
+
+        breaker.record_result::<()>(&Err(ApplicationError::UpstreamUnauthorized));
+        breaker.record_result::<()>(&Err(outage()));
+        breaker.record_result::<()>(&Err(ApplicationError::UpstreamRateLimited));
+        breaker.record_result::<()>(&Err(outage()));
+        assert_eq!(breaker.state(), BreakerState::Closed);
+
+        breaker.record_result::<()>(&Err(outage()));
+        assert_eq!(breaker.state(), BreakerState::Open);
+
+        // a probe shed by our own rate limiter leaves the way clear for the next one
+        sleep(breaker.remaining() + Duration::from_millis(2));
+        breaker
+            .check()
+            .unwrap()
+            .record_result::<()>(&Err(ApplicationError::RateLimited { retry_after_ms: 5 }));
+        let _probe = breaker.check().unwrap();
+        assert_eq!(breaker.state(), BreakerState::HalfOpen);
+    }
+
+    #[test]
+    fn failed_probes_back_off() {
+        let breaker = breaker();
+        breaker.record(false);
+        breaker.record(false);
+
+        for expected in [40, 50] {
+            sleep(breaker.remaining() + Duration::from_millis(2));
+            breaker.check().unwrap().record_result::<()>(&Err(outage()));
+
+            assert_eq!(breaker.state(), BreakerState::Open);
+            let remaining = breaker.remaining();
+            assert!(
+                remaining > Duration::from_millis(expected - 10),
+                "{remaining:?}"
+            );
+            assert!(remaining <= Duration::from_millis(expected));
+        }
+    }
+}
+
+/// When the circuit breaker trips, read from `schwab.circuit_breaker`.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct BreakerConfig {
+    /// consecutive failed calls that open the circuit
+    pub failure_threshold: u32,
+    /// how long the circuit first stays open before letting a probe through
+    pub open_ms: u64,
+    /// cap for the open period, which doubles with every failed probe
+    pub max_open_ms: u64,
+}
+
+impl Default for BreakerConfig {
+    fn default() -> Self {
+        Self {
+            failure_threshold: 5,
+            open_ms: 5_000,
+            max_open_ms: 60_000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BreakerState {
+    /// calls go through
+    Closed,
+    /// calls fail fast until the open period is over
+    Open,
+    /// a single probe call is deciding whether to close again
+    HalfOpen,
+}
+
+#[derive(Debug)]
+struct Circuit {
+    state: BreakerState,
+    /// consecutive failures while closed
+    failures: u32,
+    open_for: Duration,
+    opened: Instant,
+}
+
+/// Fails calls fast while the API behind it keeps failing, so that an outage
+/// costs one probe per open period instead of a request per poll.
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    config: BreakerConfig,
+    circuit: Mutex<Circuit>,
+}
+
+impl CircuitBreaker {
+    pub fn new(config: BreakerConfig) -> Self {
+        Self {
+            config,
+            circuit: Mutex::new(Circuit {
+                state: BreakerState::Closed,
+                failures: 0,
+                open_for: Duration::from_millis(config.open_ms),
+                opened: Instant::now(),
+            }),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Circuit> {
+        self.circuit.lock().expect("circuit breaker lock poisoned")
+    }
+
+    /// Whether a call may go out now. Once the open period is over, the first
+    /// caller becomes the half-open probe and everyone else keeps failing fast.
+    ///
+    /// The call's result goes to the returned [`Permit`]; a probe whose permit is
+    /// dropped unrecorded, e.g. because its future was cancelled, is abandoned.
+    pub fn check(&self) -> Result<Permit<'_>, ApplicationError> {
+        let mut circuit = self.lock();
+
+        let unavailable = |remaining: Duration| ApplicationError::UpstreamUnavailable {
+            retry_after_ms: remaining.as_millis().try_into().unwrap_or(u64::MAX),
+        };
+
+        let permit = |probe| Permit {
+            breaker: self,
+            probe,
+        };
+
+        match circuit.state {
+            BreakerState::Closed => Ok(permit(false)),
+            BreakerState::Open => {
+                let remaining = circuit.open_for.saturating_sub(circuit.opened.elapsed());
+                if remaining.is_zero() {
+                    circuit.state = BreakerState::HalfOpen;
+                    Ok(permit(true))
+                } else {
+                    Err(unavailable(remaining))
+                }
+            }
+            BreakerState::HalfOpen => Err(unavailable(Duration::ZERO)),
+        }
+    }
+
+    /// Record how an allowed call went.
+    pub fn record(&self, success: bool) {
+        let mut circuit = self.lock();
+
+        if success {
+            if circuit.state != BreakerState::Closed {
+                info!("Schwab market data recovered; closing the circuit");
+            }
+            circuit.state = BreakerState::Closed;
+            circuit.failures = 0;
+            circuit.open_for = Duration::from_millis(self.config.open_ms);
+            return;
+        }
+
+        match circuit.state {
+            BreakerState::Closed => {
+                circuit.failures += 1;
+                if circuit.failures < self.config.failure_threshold.max(1) {
+                    return;
+                }
+            }
+            BreakerState::HalfOpen => {
+                circuit.open_for = (circuit.open_for * 2).min(Duration::from_millis(
+                    self.config.max_open_ms.max(self.config.open_ms),
+                ));
+            }
+            // a call that started before the circuit opened
+            BreakerState::Open => return,
+        }
+
+        warn!(
+            "Schwab market data keeps failing; opening the circuit for {:?}",
+            circuit.open_for
+        );
+        circuit.state = BreakerState::Open;
+        circuit.opened = Instant::now();
+    }
+
+    /// Record a call's result: an answer from Schwab counts as success unless it
+    /// says Schwab is failing, and calls that never went out don't count at all.
+    pub fn record_result<T>(&self, result: &Result<T, ApplicationError>) {
+        match result {
+            Ok(_) => self.record(true),
+            Err(error) if is_outage(error) => self.record(false),
+            Err(ApplicationError::RateLimited { .. }) => self.abandon(),
+            Err(_) => self.record(true),
+        }
+    }
+
+    /// Give up a half-open probe that never reached Schwab, so the next call probes instead.
+    fn abandon(&self) {
+        let mut circuit = self.lock();
+
+        if circuit.state == BreakerState::HalfOpen {
+            // still past the open period, so the next check lets a probe through
+            circuit.state = BreakerState::Open;
+        }
+    }
+
+    pub fn state(&self) -> BreakerState {
+        self.lock().state
+    }
+
+    /// Time left until a probe may go out; zero unless open.
+    pub fn remaining(&self) -> Duration {
+        let circuit = self.lock();
+
+        match circuit.state {
+            BreakerState::Open => circuit.open_for.saturating_sub(circuit.opened.elapsed()),
+            _ => Duration::ZERO,
+        }
+    }
+}
+
+/// A call let through by [`CircuitBreaker::check`], waiting for its result.
+#[derive(Debug)]
+#[must_use = "dropping a permit abandons the call it was for"]
+pub struct Permit<'b> {
+    breaker: &'b CircuitBreaker,
+    /// whether this call is the half-open probe
+    probe: bool,
+}
+
+impl Permit<'_> {
+    pub fn record_result<T>(mut self, result: &Result<T, ApplicationError>) {
+        self.probe = false;
+        self.breaker.record_result(result);
+    }
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        if self.probe {
+            self.breaker.abandon();
+        }
+    }
+}
+
+/// Failures that mean Schwab is down rather than that the request was bad.
+pub fn is_outage(error: &ApplicationError) -> bool {
+    match error {
+        ApplicationError::Network(_) => true,
+        ApplicationError::UpstreamFailed { status, .. } => *status >= 500,
+        ApplicationError::Retried { error, .. } => is_outage(error),
+        _ => false,
+    }
+}
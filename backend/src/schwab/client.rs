@@ -0,0 +1,382 @@
+use reqwest::{
+    Client, Method, RequestBuilder, Response, StatusCode,
+    header::{ACCEPT, AUTHORIZATION, HeaderMap, HeaderValue},
+};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use rocket::tokio::time::sleep;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    errors::ApplicationError,
+    oauth::Credentials,
+    schwab::{
+        breaker::{BreakerConfig, BreakerState, CircuitBreaker},
+        limiter::{RateLimitConfig, RateLimiter},
+        retry::{self, RetryConfig},
+        schema::ApiErrorResponse,
+    },
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BAD_REQUEST: &str = r#"{"errors":[{"id":"a208a739","status":"400","title":"Bad Request","detail":"Search combination should have min of 1.","source":{"pointer":["/data/attributes/symbols","/data/attributes/cusips"]}}]}"#;
+
+    #[test]
+    fn bad_request_keeps_detail_and_pointers() {
+        let error = upstream_error(StatusCode::BAD_REQUEST, BAD_REQUEST);
+
+        let ApplicationError::UpstreamBadRequest { detail, pointers } = error else {
+            panic!("expected UpstreamBadRequest, got {error:?}");
+        };
+        assert_eq!(detail, "Search combination should have min of 1.");
+        assert_eq!(
+            pointers,
+            ["/data/attributes/symbols", "/data/attributes/cusips"]
+        );
+    }
+
+    #[test]
+    fn statuses_map_to_variants() {
+        assert!(matches!(
+            upstream_error(StatusCode::UNAUTHORIZED, ""),
+            ApplicationError::UpstreamUnauthorized
+        ));
+        assert!(matches!(
+            upstream_error(StatusCode::TOO_MANY_REQUESTS, "not json"),
+            ApplicationError::UpstreamRateLimited
+        ));
+        assert!(matches!(
+            upstream_error(StatusCode::SERVICE_UNAVAILABLE, "<html>"),
+            ApplicationError::UpstreamFailed { status: 503, ref detail } if detail == "Service Unavailable"
+        ));
+    }
+}
+
+const DEFAULT_TRADER_API: &str = "https://api.schwabapi.com/trader/v1";
+const DEFAULT_MARKET_DATA_API: &str = "https://api.schwabapi.com/marketdata/v1";
+
+/// Anything that can hand out a bearer token for a Schwab request.
+pub trait TokenSource: Send + Sync {
+    /// `None` if there is no usable token, e.g. because it expired.
+    fn access_token(&self) -> Option<&str>;
+}
+
+impl TokenSource for Credentials {
+    fn access_token(&self) -> Option<&str> {
+        Credentials::access_token(self)
+    }
+}
+
+/// Where the Schwab APIs live, read from the `schwab` config table.
+///
+/// Point these at a local server to run against a mock.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct SchwabApiConfig {
+    pub trader_api: String,
+    pub market_data_api: String,
+    pub rate_limit: RateLimitConfig,
+    pub retry: RetryConfig,
+    /// guards market data only; trader calls are user initiated and few
+    pub circuit_breaker: BreakerConfig,
+    /// keep, count and log quote fields the schema doesn't know, see `/u/diagnostics/schema`;
+    /// they are skipped without it
+    pub strict_schema: bool,
+}
+
+impl Default for SchwabApiConfig {
+    fn default() -> Self {
+        Self {
+            trader_api: DEFAULT_TRADER_API.to_owned(),
+            market_data_api: DEFAULT_MARKET_DATA_API.to_owned(),
+            rate_limit: RateLimitConfig::default(),
+            retry: RetryConfig::default(),
+            circuit_breaker: BreakerConfig::default(),
+            strict_schema: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Api {
+    Trader,
+    MarketData,
+}
+
+/// The one HTTP client every Schwab API call goes through.
+///
+/// Owns a pooled [`Client`] with default headers set, the app-wide
+/// [`RateLimiter`] and the market data [`CircuitBreaker`], so it should be
+/// created once and shared.
+#[derive(Debug, Clone)]
+pub struct SchwabClient {
+    http: Client,
+    config: SchwabApiConfig,
+    limiter: Arc<RateLimiter>,
+    breaker: Arc<CircuitBreaker>,
+}
+
+impl SchwabClient {
+    pub fn new(config: SchwabApiConfig) -> Result<Self, reqwest::Error> {
+        let mut headers = HeaderMap::new();
+        headers.insert(ACCEPT, HeaderValue::from_static("application/json"));
+
+        let http = Client::builder()
+            .default_headers(headers)
+            .user_agent(concat!(
+                env!("CARGO_PKG_NAME"),
+                "/",
+                env!("CARGO_PKG_VERSION")
+            ))
+            .build()?;
+
+        Ok(Self {
+            http,
+            limiter: Arc::new(RateLimiter::new(config.rate_limit)),
+            breaker: Arc::new(CircuitBreaker::new(config.circuit_breaker)),
+            config,
+        })
+    }
+
+    pub fn config(&self) -> &SchwabApiConfig {
+        &self.config
+    }
+
+    pub fn limiter(&self) -> &RateLimiter {
+        &self.limiter
+    }
+
+    /// The circuit breaker guarding `api`, if any.
+    pub fn breaker(&self, api: Api) -> Option<&CircuitBreaker> {
+        match api {
+            Api::Trader => None,
+            Api::MarketData => Some(&self.breaker),
+        }
+    }
+
+    pub fn url(&self, api: Api, path: &str) -> String {
+        let base = match api {
+            Api::Trader => &self.config.trader_api,
+            Api::MarketData => &self.config.market_data_api,
+        };
+
+        format!(
+            "{}/{}",
+            base.trim_end_matches('/'),
+            path.trim_start_matches('/')
+        )
+    }
+
+    /// Start an authorized request to `path` on `api`.
+    pub fn request(
+        &self,
+        method: Method,
+        api: Api,
+        path: &str,
+        token: &dyn TokenSource,
+    ) -> Result<SchwabRequest<'_>, ApplicationError> {
+        let access_token = token
+            .access_token()
+            .ok_or(ApplicationError::MissingAuthentication)?;
+
+        let builder = self
+            .http
+            .request(method.clone(), self.url(api, path))
+            .header(AUTHORIZATION, format!("Bearer {access_token}"));
+
+        Ok(SchwabRequest {
+            client: self,
+            api,
+            method,
+            builder,
+        })
+    }
+
+    pub fn get(
+        &self,
+        api: Api,
+        path: &str,
+        token: &dyn TokenSource,
+    ) -> Result<SchwabRequest<'_>, ApplicationError> {
+        self.request(Method::GET, api, path, token)
+    }
+}
+
+/// A request being built by [`SchwabClient`], rate limited once sent.
+#[derive(Debug)]
+pub struct SchwabRequest<'c> {
+    client: &'c SchwabClient,
+    api: Api,
+    method: Method,
+    builder: RequestBuilder,
+}
+
+impl SchwabRequest<'_> {
+    pub fn query<T: Serialize + ?Sized>(mut self, query: &T) -> Self {
+        self.builder = self.builder.query(query);
+        self
+    }
+
+    /// Send, turning any non-2xx answer into the matching error.
+    ///
+    /// `GET`s that fail transiently are retried per the client's [`RetryConfig`],
+    /// in which case the final error is wrapped in [`ApplicationError::Retried`].
+    /// While the API's circuit breaker is open this fails fast with
+    /// [`ApplicationError::UpstreamUnavailable`], as does the call that opens it.
+    pub async fn send(self) -> Result<Response, ApplicationError> {
+        let Some(breaker) = self.client.breaker(self.api) else {
+            return self.send_with_retries().await;
+        };
+
+        let permit = breaker.check()?;
+        let result = self.send_with_retries().await;
+        permit.record_result(&result);
+
+        match result {
+            Err(error) if breaker.state() == BreakerState::Open => {
+                error!("giving up on Schwab until the circuit closes: {error}");
+                Err(ApplicationError::UpstreamUnavailable {
+                    retry_after_ms: breaker
+                        .remaining()
+                        .as_millis()
+                        .try_into()
+                        .unwrap_or(u64::MAX),
+                })
+            }
+            result => result,
+        }
+    }
+
+    async fn send_with_retries(self) -> Result<Response, ApplicationError> {
+        let Self {
+            client,
+            api,
+            method,
+            builder,
+        } = self;
+
+        let policy = client.config.retry;
+        let deadline = Instant::now() + policy.deadline();
+        let max_attempts = if method == Method::GET {
+            policy.max_attempts.max(1)
+        } else {
+            1
+        };
+
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+
+            let Some(request) = builder.try_clone() else {
+                // a streaming body can't be replayed, so it only gets the one attempt
+                return attempt(client, api, builder, deadline)
+                    .await
+                    .map_err(|(error, _)| error);
+            };
+
+            let (error, retry_after) = match attempt(client, api, request, deadline).await {
+                Ok(response) => return Ok(response),
+                Err(failure) => failure,
+            };
+
+            let delay = retry_after.unwrap_or_else(|| policy.backoff(attempts));
+
+            if attempts >= max_attempts
+                || !retry::is_retryable(&error)
+                || Instant::now() + delay >= deadline
+            {
+                if attempts == 1 {
+                    return Err(error);
+                }
+
+                error!("{method} {api:?} failed after {attempts} attempts: {error}");
+                return Err(ApplicationError::Retried {
+                    attempts,
+                    error: Box::new(error),
+                });
+            }
+
+            warn!("{method} {api:?} failed, retrying in {delay:?}: {error}");
+            sleep(delay).await;
+        }
+    }
+
+    /// Send and read the whole body.
+    pub async fn text(self) -> Result<String, ApplicationError> {
+        self.send()
+            .await?
+            .text()
+            .await
+            .map_err(ApplicationError::Network)
+    }
+}
+
+/// One rate limited try at `request`, giving up at `deadline`. Failures come
+/// with the wait Schwab asked for in `Retry-After`, if any.
+async fn attempt(
+    client: &SchwabClient,
+    api: Api,
+    request: RequestBuilder,
+    deadline: Instant,
+) -> Result<Response, (ApplicationError, Option<Duration>)> {
+    client
+        .limiter
+        .bucket(api)
+        .acquire()
+        .await
+        .map_err(|e| (e, None))?;
+
+    let response = request
+        .timeout(deadline.saturating_duration_since(Instant::now()))
+        .send()
+        .await
+        .map_err(|e| (ApplicationError::Network(e), None))?;
+
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+
+    let retry_after =
+        retry::retry_after(response.headers()).filter(|_| retry::is_retryable_status(status));
+    let body = response
+        .text()
+        .await
+        .map_err(|e| (ApplicationError::Network(e), None))?;
+
+    Err((upstream_error(status, &body), retry_after))
+}
+
+/// The error for a non-2xx `status`, with details from Schwab's `errors` body if it sent one.
+fn upstream_error(status: StatusCode, body: &str) -> ApplicationError {
+    let errors = serde_json::from_str::<ApiErrorResponse>(body).ok();
+
+    let detail = errors
+        .as_ref()
+        .map(ApiErrorResponse::detail)
+        .filter(|detail| !detail.is_empty())
+        .unwrap_or_else(|| status.canonical_reason().unwrap_or_default().to_owned());
+
+    match status {
+        StatusCode::UNAUTHORIZED => ApplicationError::UpstreamUnauthorized,
+        StatusCode::TOO_MANY_REQUESTS => ApplicationError::UpstreamRateLimited,
+        StatusCode::BAD_REQUEST => ApplicationError::UpstreamBadRequest {
+            detail,
+            pointers: errors
+                .as_ref()
+                .map(ApiErrorResponse::pointers)
+                .unwrap_or_default(),
+        },
+        _ => ApplicationError::UpstreamFailed {
+            status: status.as_u16(),
+            detail,
+        },
+    }
+}
@@ -0,0 +1,447 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::{LazyLock, Mutex},
+};
+
+use chrono::{DateTime, Utc};
+use schemars::{JsonSchema, schema_for};
+use serde::{Deserialize, Deserializer, Serialize, de::IgnoredAny};
+use serde_json::Value;
+
+use crate::schwab::schema::{
+    EquityResponse, ExtendedMarket, ForexResponse, Fundamental, FutureOptionResponse,
+    FutureResponse, IndexResponse, MutualFundResponse, OptionResponse, QuoteEquity, QuoteError,
+    QuoteForex, QuoteFuture, QuoteFutureOption, QuoteIndex, QuoteMutualFund, QuoteOption,
+    QuoteResponse, QuoteResponseObject, ReferenceEquity, ReferenceForex, ReferenceFuture,
+    ReferenceFutureOption, ReferenceIndex, ReferenceMutualFund, ReferenceOption, RegularMarket,
+};
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, path::Path};
+
+    use serde_json::json;
+
+    use super::*;
+
+    /// Schwab responses, one `QuoteResponse` per file. Hand-written until
+    /// [`record_corpus`] is run against Schwab, so for now they only catch
+    /// changes on our side.
+    const CORPUS: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/quotes");
+
+    /// One symbol per asset class that doesn't expire. Options and future
+    /// options do, so current ones have to be passed in `SCHWAB_FIXTURE_SYMBOLS`.
+    const RECORDED_SYMBOLS: &str = "AAPL,$SPX,VFIAX,EUR/USD,/ES";
+
+    /// The same JSON with every number as an `f64`, since prices re-serialize
+    /// as floats even when Schwab sent a whole number.
+    fn normalize_numbers(value: Value) -> Value {
+        match value {
+            Value::Number(number) => number.as_f64().map_or(Value::Number(number), Value::from),
+            Value::Array(values) => values.into_iter().map(normalize_numbers).collect(),
+            Value::Object(map) => map
+                .into_iter()
+                .map(|(key, value)| (key, normalize_numbers(value)))
+                .collect(),
+            other => other,
+        }
+    }
+
+    fn read_corpus() -> Vec<(String, Value)> {
+        let mut files: Vec<_> = fs::read_dir(CORPUS)
+            .expect("fixture corpus should exist")
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+            .collect();
+        files.sort();
+
+        files
+            .iter()
+            .map(|path| {
+                let name = path.file_name().unwrap().to_string_lossy().into_owned();
+                (name, read_fixture(path))
+            })
+            .collect()
+    }
+
+    fn read_fixture(path: &Path) -> Value {
+        let text = fs::read_to_string(path).unwrap();
+        serde_json::from_str(&text).unwrap_or_else(|e| panic!("{}: {e}", path.display()))
+    }
+
+    /// Replaces the corpus with live responses, one file per asset class:
+    /// `SCHWAB_ACCESS_TOKEN=... cargo test -p backend record_corpus -- --ignored`.
+    ///
+    /// Quotes hold nothing about the account that fetched them, and the token
+    /// itself is never written, so the files need no further scrubbing.
+    #[rocket::async_test]
+    #[ignore = "needs a Schwab access token"]
+    async fn record_corpus() {
+        let token = env::var("SCHWAB_ACCESS_TOKEN").expect("SCHWAB_ACCESS_TOKEN should be set");
+        let symbols =
+            env::var("SCHWAB_FIXTURE_SYMBOLS").unwrap_or_else(|_| RECORDED_SYMBOLS.to_owned());
+
+        let response: Value = reqwest::Client::new()
+            .get("https://api.schwabapi.com/marketdata/v1/quotes")
+            .bearer_auth(token)
+            .query(&[
+                ("symbols", symbols.as_str()),
+                ("fields", "quote,fundamental,extended,reference,regular"),
+                ("indicative", "false"),
+            ])
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .expect("Schwab should answer the quotes request")
+            .json()
+            .await
+            .expect("Schwab should answer with JSON");
+
+        let mut files = BTreeMap::<String, serde_json::Map<String, Value>>::new();
+        for (symbol, quote) in response.as_object().expect("quotes should be an object") {
+            let name = quote["assetMainType"]
+                .as_str()
+                .map_or_else(|| "errors".to_owned(), str::to_ascii_lowercase);
+
+            files
+                .entry(name)
+                .or_default()
+                .insert(symbol.clone(), quote.clone());
+        }
+
+        for (name, quotes) in files {
+            let json = serde_json::to_string_pretty(&quotes).unwrap() + "\n";
+            fs::write(format!("{CORPUS}/{name}.json"), json).unwrap();
+        }
+    }
+
+    #[test]
+    fn fixture_responses_round_trip() {
+        let corpus = read_corpus();
+        assert!(!corpus.is_empty(), "no fixtures in {CORPUS}");
+
+        for (name, json) in corpus {
+            let response = from_value_with_unknowns(&json)
+                .unwrap_or_else(|e| panic!("{name} no longer parses: {e}"));
+
+            let drift = SchemaDrift::default();
+            drift.record(&response);
+            let unknown: Vec<_> = drift
+                .report()
+                .into_iter()
+                .map(|field| format!("{}.{}", field.type_name, field.field))
+                .collect();
+            assert!(unknown.is_empty(), "{name} has unknown fields: {unknown:?}");
+
+            assert_eq!(
+                normalize_numbers(serde_json::to_value(&response).unwrap()),
+                normalize_numbers(json),
+                "{name} changed on the way through QuoteResponse"
+            );
+        }
+    }
+
+    #[test]
+    fn unknown_fields_are_counted_per_type_and_symbol() {
+        let chunk = |json: Value| from_value_with_unknowns(&json).unwrap();
+        let equity = |symbol: &str| {
+            json!({
+                "assetMainType": "EQUITY", "ssid": 1, "symbol": symbol, "realtime": true,
+                "quote": { "lastPrice": 200.5, "lastPriceExchange": "XNAS" },
+                "newSection": { "a": 1 }
+            })
+        };
+
+        let response = chunk(json!({ "AAPL": equity("AAPL"), "MSFT": equity("MSFT") }));
+        let QuoteResponseObject::Equity(aapl) = &response.quotes["AAPL"] else {
+            panic!("AAPL should be an equity");
+        };
+        assert_eq!(
+            aapl.quote
+                .as_ref()
+                .unwrap()
+                .unknown
+                .0
+                .get("lastPriceExchange"),
+            Some(&json!("XNAS"))
+        );
+
+        let drift = SchemaDrift::default();
+        drift.record(&response);
+        drift.record(&chunk(json!({ "AAPL": equity("AAPL") })));
+
+        let report = drift.report();
+        assert_eq!(report.len(), 2);
+
+        let field = &report[0];
+        assert_eq!(
+            (field.type_name, field.field.as_str()),
+            ("EquityResponse", "newSection")
+        );
+        assert_eq!(field.count, 3);
+        assert_eq!(field.example, json!({ "a": 1 }));
+
+        let field = &report[1];
+        assert_eq!(
+            (field.type_name, field.field.as_str()),
+            ("QuoteEquity", "lastPriceExchange")
+        );
+        assert_eq!(field.symbols["AAPL"], 2);
+        assert_eq!(field.symbols["MSFT"], 1);
+
+        // unknown keys are passed through rather than dropped
+        assert_eq!(
+            serde_json::to_value(&response).unwrap()["MSFT"]["quote"]["lastPriceExchange"],
+            "XNAS"
+        );
+
+        // unless nobody is looking for them
+        let response: QuoteResponse =
+            serde_json::from_value(json!({ "AAPL": equity("AAPL") })).unwrap();
+        let drift = SchemaDrift::default();
+        drift.record(&response);
+        assert!(drift.report().is_empty());
+        assert!(
+            serde_json::to_value(&response).unwrap()["AAPL"]
+                .get("newSection")
+                .is_none()
+        );
+    }
+}
+
+/// Most symbols tracked per unknown field; later ones still add to its count.
+const MAX_SYMBOLS_PER_FIELD: usize = 100;
+
+/// Read a `QuoteResponse` with the keys each type didn't recognize kept in its
+/// [`UnknownFields`], for `schwab.strict_schema`.
+///
+/// Plain deserialization skips them, so that nothing else pays to hold on to them.
+pub fn from_value_with_unknowns(raw: &Value) -> Result<QuoteResponse, serde_json::Error> {
+    let mut response = QuoteResponse::deserialize(raw)?;
+
+    for (symbol, object) in &mut response.quotes {
+        if let Some(raw) = raw.get(symbol) {
+            object.capture_unknowns(raw);
+        }
+    }
+
+    Ok(response)
+}
+
+/// The keys `T` reads, as listed in its schema.
+fn known_keys<T: JsonSchema>() -> BTreeSet<String> {
+    schema_for!(T)
+        .get("properties")
+        .and_then(Value::as_object)
+        .map(|properties| properties.keys().cloned().collect())
+        .unwrap_or_default()
+}
+
+/// Keys a schema type didn't recognize, with their values.
+///
+/// Every quote response type flattens one of these in. Read through
+/// [`from_value_with_unknowns`], fields Schwab adds or renames are kept, and
+/// passed on to clients, instead of silently dropped; otherwise this stays empty.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, JsonSchema)]
+#[serde(transparent)]
+pub struct UnknownFields(BTreeMap<String, Value>);
+
+impl UnknownFields {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl<'de> Deserialize<'de> for UnknownFields {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        IgnoredAny::deserialize(deserializer)?;
+        Ok(Self::default())
+    }
+}
+
+/// One key a schema type didn't recognize.
+#[derive(Debug)]
+pub struct UnknownField<'a> {
+    pub type_name: &'static str,
+    pub field: &'a str,
+    pub value: &'a Value,
+}
+
+/// A schema type that can list the keys it, or any block inside it, didn't recognize.
+pub trait Unknowns {
+    fn unknown_fields<'a>(&'a self, found: &mut Vec<UnknownField<'a>>);
+
+    /// Keep the keys of `raw`, the JSON this was read from, that neither it nor
+    /// any block inside it recognizes.
+    fn capture_unknowns(&mut self, raw: &Value);
+}
+
+/// Implements [`Unknowns`] for schema types, given the optional blocks each one contains.
+macro_rules! unknowns {
+    ($($ty:ident $(=> $($child:ident),+)?;)+) => {$(
+        impl Unknowns for $ty {
+            fn unknown_fields<'a>(&'a self, found: &mut Vec<UnknownField<'a>>) {
+                found.extend(self.unknown.0.iter().map(|(field, value)| UnknownField {
+                    type_name: stringify!($ty),
+                    field,
+                    value,
+                }));
+                $($(
+                    if let Some(child) = &self.$child {
+                        child.unknown_fields(found);
+                    }
+                )+)?
+            }
+
+            fn capture_unknowns(&mut self, raw: &Value) {
+                static KNOWN: LazyLock<BTreeSet<String>> = LazyLock::new(known_keys::<$ty>);
+
+                let Some(raw) = raw.as_object() else {
+                    return;
+                };
+                self.unknown.0 = raw
+                    .iter()
+                    .filter(|(key, _)| !KNOWN.contains(*key))
+                    .map(|(key, value)| (key.clone(), value.clone()))
+                    .collect();
+
+                $($(
+                    if let (Some(child), Some(raw)) = (&mut self.$child, raw.get(stringify!($child))) {
+                        child.capture_unknowns(raw);
+                    }
+                )+)?
+            }
+        }
+    )+};
+}
+
+unknowns! {
+    EquityResponse => extended, fundamental, quote, reference, regular;
+    OptionResponse => quote, reference;
+    ForexResponse => quote, reference;
+    FutureResponse => quote, reference;
+    FutureOptionResponse => quote, reference;
+    IndexResponse => quote, reference;
+    MutualFundResponse => fundamental, quote, reference;
+    ExtendedMarket;
+    Fundamental;
+    RegularMarket;
+    QuoteEquity;
+    QuoteOption;
+    QuoteForex;
+    QuoteFuture;
+    QuoteFutureOption;
+    QuoteIndex;
+    QuoteMutualFund;
+    ReferenceEquity;
+    ReferenceOption;
+    ReferenceForex;
+    ReferenceFuture;
+    ReferenceFutureOption;
+    ReferenceIndex;
+    ReferenceMutualFund;
+    QuoteError;
+}
+
+impl Unknowns for QuoteResponseObject {
+    fn unknown_fields<'a>(&'a self, found: &mut Vec<UnknownField<'a>>) {
+        match self {
+            Self::Equity(response) => response.unknown_fields(found),
+            Self::Option(response) => response.unknown_fields(found),
+            Self::Forex(response) => response.unknown_fields(found),
+            Self::Future(response) => response.unknown_fields(found),
+            Self::FutureOption(response) => response.unknown_fields(found),
+            Self::Index(response) => response.unknown_fields(found),
+            Self::MutualFund(response) => response.unknown_fields(found),
+            Self::Error(error) => error.unknown_fields(found),
+        }
+    }
+
+    fn capture_unknowns(&mut self, raw: &Value) {
+        match self {
+            Self::Equity(response) => response.capture_unknowns(raw),
+            Self::Option(response) => response.capture_unknowns(raw),
+            Self::Forex(response) => response.capture_unknowns(raw),
+            Self::Future(response) => response.capture_unknowns(raw),
+            Self::FutureOption(response) => response.capture_unknowns(raw),
+            Self::Index(response) => response.capture_unknowns(raw),
+            Self::MutualFund(response) => response.capture_unknowns(raw),
+            Self::Error(error) => error.capture_unknowns(raw),
+        }
+    }
+}
+
+/// How often one unknown field has shown up, and for which symbols.
+#[derive(Debug, Clone, Serialize)]
+pub struct FieldDrift {
+    /// the schema type it appeared on, e.g. `QuoteEquity`
+    pub type_name: &'static str,
+    pub field: String,
+    pub count: u64,
+    /// occurrences per symbol, for up to [`MAX_SYMBOLS_PER_FIELD`] symbols
+    pub symbols: BTreeMap<String, u64>,
+    /// the value it had the first time
+    pub example: Value,
+    pub first_seen: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+}
+
+/// Unknown fields seen in Schwab's responses since startup, for `schwab.strict_schema`.
+#[derive(Debug, Default)]
+pub struct SchemaDrift {
+    fields: Mutex<BTreeMap<(&'static str, String), FieldDrift>>,
+}
+
+impl SchemaDrift {
+    /// Count the unknown fields in `response`, logging each the first time it shows up.
+    pub fn record(&self, response: &QuoteResponse) {
+        let now = Utc::now();
+        let mut fields = self.fields.lock().expect("schema drift lock poisoned");
+
+        for (symbol, object) in &response.quotes {
+            let mut found = Vec::new();
+            object.unknown_fields(&mut found);
+
+            for UnknownField {
+                type_name,
+                field,
+                value,
+            } in found
+            {
+                let drift = fields
+                    .entry((type_name, field.to_owned()))
+                    .or_insert_with(|| {
+                        warn!("Schwab sent an unknown field {type_name}.{field}, e.g. for {symbol}: {value}");
+
+                        FieldDrift {
+                            type_name,
+                            field: field.to_owned(),
+                            count: 0,
+                            symbols: BTreeMap::new(),
+                            example: value.clone(),
+                            first_seen: now,
+                            last_seen: now,
+                        }
+                    });
+
+                drift.count += 1;
+                drift.last_seen = now;
+                if let Some(count) = drift.symbols.get_mut(symbol) {
+                    *count += 1;
+                } else if drift.symbols.len() < MAX_SYMBOLS_PER_FIELD {
+                    drift.symbols.insert(symbol.clone(), 1);
+                }
+            }
+        }
+    }
+
+    /// Every unknown field seen so far, by type and then field name.
+    pub fn report(&self) -> Vec<FieldDrift> {
+        self.fields
+            .lock()
+            .expect("schema drift lock poisoned")
+            .values()
+            .cloned()
+            .collect()
+    }
+}
@@ -1,154 +1,220 @@
-use std::collections::HashSet;
-
-use rocket::form::FromForm;
+use rocket::form::{self, FromForm};
 use rocket::futures::{SinkExt, StreamExt};
-use rocket::tokio::select;
-use rocket::{State, http::CookieJar, serde::json::Json};
+use rocket::tokio::{select, sync::broadcast::error::RecvError};
+use rocket::{
+    State,
+    serde::json::{Json, Value, json},
+};
 use rocket_oauth2::OAuth2;
-use serde::Deserialize;
-use serde_json::Value;
 use ws::{Message, WebSocket};
 
 use crate::{
+    broker::{
+        Account, BrokerQuotes, DynBroker, FormattedQuotes, HistoryPeriod, Order, OrderRequest,
+        PriceHistory, QuoteFormat,
+    },
     errors::ApplicationError,
-    oauth::{AUTH_COOKIE_NAME, Credentials, Schwab},
-    quotes::QuotesState,
-    schwab::{SchwabUsers, get_user, schema::QuoteResponse},
+    oauth::{Schwab, SessionStatus},
+    quotes::{
+        QuotesState,
+        protocol::{
+            self, ClientMessage, ClientRequest, PROTOCOL_VERSION, ServerBody, ServerMessage,
+            StreamError, StreamStatus,
+        },
+    },
+    schwab::{drift::FieldDrift, limiter::RateLimitStatus},
+    session::{AuthenticatedUser, SessionStore},
+    symbol::{InvalidSymbol, SymbolList},
 };
 
 #[get("/user")]
 pub async fn user(
-    oauth2: OAuth2<Schwab>,
-    cookies: &CookieJar<'_>,
-) -> Result<Json<SchwabUsers>, ApplicationError> {
-    let auth = cookies
-        .get_private(AUTH_COOKIE_NAME)
-        .ok_or(ApplicationError::MissingAuthentication)?;
+    broker: &State<DynBroker>,
+    user: AuthenticatedUser,
+) -> Result<Json<Vec<Account>>, ApplicationError> {
+    let accounts = broker.accounts(&user.credentials).await?;
+
+    Ok(Json::from(accounts))
+}
+
+/// Requests left before the app hits its broker rate limits; `null` for brokers without any.
+#[get("/rate_limit")]
+pub fn rate_limit(
+    broker: &State<DynBroker>,
+    _user: AuthenticatedUser,
+) -> Json<Option<RateLimitStatus>> {
+    Json(broker.rate_limits())
+}
 
-    let mut credentials =
-        Credentials::decode(auth.value()).map_err(ApplicationError::InvalidCredentials)?;
+/// Unknown fields seen in quote responses; `null` unless `schwab.strict_schema` is on.
+#[get("/diagnostics/schema")]
+pub fn schema_drift(
+    broker: &State<DynBroker>,
+    _user: AuthenticatedUser,
+) -> Json<Option<Vec<FieldDrift>>> {
+    Json(broker.schema_drift())
+}
+
+#[get("/history/<symbol>?<period>")]
+pub async fn history(
+    broker: &State<DynBroker>,
+    user: AuthenticatedUser,
+    symbol: &str,
+    period: Option<HistoryPeriod>,
+) -> Result<Json<PriceHistory>, ApplicationError> {
+    let history = broker
+        .price_history(
+            &user.credentials,
+            symbol,
+            period.unwrap_or(HistoryPeriod::Day),
+        )
+        .await?;
+
+    Ok(Json::from(history))
+}
 
-    let user = get_user(&mut credentials, &oauth2).await?;
+#[get("/accounts/<account>/orders")]
+pub async fn orders(
+    broker: &State<DynBroker>,
+    user: AuthenticatedUser,
+    account: &str,
+) -> Result<Json<Vec<Order>>, ApplicationError> {
+    let orders = broker.orders(&user.credentials, account).await?;
 
-    Ok(Json::from(SchwabUsers(user)))
+    Ok(Json::from(orders))
 }
 
+/// Only JSON bodies are accepted, which a cross-site form can't send.
+#[post("/accounts/<account>/orders", format = "json", data = "<order>")]
+pub async fn place_order(
+    broker: &State<DynBroker>,
+    user: AuthenticatedUser,
+    account: &str,
+    order: Json<OrderRequest>,
+) -> Result<Json<Order>, ApplicationError> {
+    let order = order.into_inner();
+    order.validate()?;
+
+    let placed = broker
+        .place_order(&user.credentials, account, order)
+        .await?;
+
+    Ok(Json::from(placed))
+}
+
+#[cfg(feature = "debug-routes")]
 #[post("/refresh_token")]
 pub async fn refresh_token_debug(
     oauth2: OAuth2<Schwab>,
-    cookies: &CookieJar<'_>,
-) -> Result<Value, ApplicationError> {
-    let auth = cookies
-        .get_private(AUTH_COOKIE_NAME)
-        .ok_or(ApplicationError::MissingAuthentication)?;
-
-    let mut credentials =
-        Credentials::decode(auth.value()).map_err(ApplicationError::InvalidCredentials)?;
+    sessions: &State<SessionStore>,
+    user: AuthenticatedUser,
+) -> Result<serde_json::Value, ApplicationError> {
+    let AuthenticatedUser {
+        session,
+        mut credentials,
+    } = user;
 
     credentials
         .refresh_access_token(&oauth2)
         .await
         .map_err(ApplicationError::InvalidCredentials)?;
 
+    sessions.save(&session, &credentials).await?;
+
     Ok(serde_json::json!({
             "success": true,
     }))
 }
 
 #[derive(FromForm)]
-pub struct QuotesQuery {
-    pub symbols: Option<String>,
+pub struct QuotesQuery<'r> {
+    /// kept as a result so that an invalid symbol can be reported as such
+    pub symbols: form::Result<'r, SymbolList>,
+    /// the broker's own format if left out
+    pub format: Option<QuoteFormat>,
 }
 
 #[get("/quotes?<q..>")]
 pub async fn quotes(
-    oauth2: OAuth2<Schwab>,
-    cookies: &CookieJar<'_>,
+    broker: &State<DynBroker>,
+    user: AuthenticatedUser,
     qm: &State<QuotesState>,
-    q: QuotesQuery,
-) -> Result<Json<QuoteResponse>, ApplicationError> {
-    let symbols_raw = q
-        .symbols
-        .ok_or_else(|| ApplicationError::MissingQueryParameters(vec!["q".to_owned()]))?;
-
-    let symbols = symbols_raw
-        .split(',')
-        .map(str::trim)
-        .map(str::to_owned)
-        .filter(|s| !s.is_empty())
-        .collect();
-
-    let auth = cookies
-        .get_private(AUTH_COOKIE_NAME)
-        .ok_or(ApplicationError::MissingAuthentication)?;
-
-    let mut credentials =
-        Credentials::decode(auth.value()).map_err(ApplicationError::InvalidCredentials)?;
-
-    if credentials.is_expired() {
-        credentials
-            .refresh_access_token(&oauth2)
-            .await
-            .map_err(ApplicationError::InvalidCredentials)?;
-    }
+    q: QuotesQuery<'_>,
+) -> Result<Json<FormattedQuotes>, ApplicationError> {
+    let format = QuoteFormat::negotiate(q.format, broker.quote_format())?;
 
-    qm.set_credentials(credentials).await;
+    let SymbolList(symbols) = q.symbols.map_err(|errors| {
+        InvalidSymbol::from_form(&errors).map_or_else(
+            || ApplicationError::MissingQueryParameters(vec!["symbols".to_owned()]),
+            ApplicationError::InvalidSymbol,
+        )
+    })?;
 
-    qm.extend_quotes(symbols).await;
+    let qm = qm.session(&user.session).await;
 
-    let mut subscription = qm.subscribe().await;
+    qm.set_credentials(user.credentials).await;
 
-    // in the loop, check if credentials are expired BEFORE recieving.
-    let response = subscription
-        .recv()
-        .await
-        .map_err(ApplicationError::ChannelBroadcastFailed)?
-        .map_err(ApplicationError::Polling)?;
+    let quotes = qm.fetch(symbols).await?;
 
-    Ok(Json::from(response))
+    Ok(Json::from(format.apply(quotes)?))
 }
 
-fn ws_err(e: ApplicationError) -> Message {
-    Message::Text(format!(r#"{{"error":{{"{e:?}":"{e}"}}}}"#))
+impl From<ServerBody> for Message {
+    fn from(value: ServerBody) -> Self {
+        let message = ServerMessage::from(value);
+        Message::Text(
+            serde_json::to_string(&message).expect("ServerMessage should be serializable"),
+        )
+    }
 }
 
-#[derive(Debug, Deserialize)]
-#[serde(crate = "rocket::serde", tag = "type", rename_all = "snake_case")]
-enum ClientMsg {
-    Add { symbols: Vec<String> },
-    Remove { symbols: Vec<String> },
-    Subscribe { symbols: Vec<String> },
-    Ping(Vec<u8>),
+/// JSON Schemas for the messages on `/u/quotes/stream`, in each direction.
+#[get("/quotes/stream/schema")]
+pub fn quotes_stream_schema() -> Json<Value> {
+    Json(json!({
+        "client": protocol::client_schema(),
+        "server": protocol::server_schema(),
+    }))
 }
 
-#[get("/quotes/stream")]
-pub async fn quotes_stream<'a, 'b: 'a>(
+/// Quotes for the symbols a client watches, in the [`protocol`] message format.
+#[get("/quotes/stream?<format>")]
+pub async fn quotes_stream<'a>(
+    broker: &State<DynBroker>,
     oauth2: OAuth2<Schwab>,
-    cookies: &'b CookieJar<'a>,
+    sessions: &'a State<SessionStore>,
     qm: &'a State<QuotesState>,
+    user: AuthenticatedUser,
+    format: Option<QuoteFormat>,
     ws: WebSocket,
-) -> ws::Channel<'a> {
-    ws.channel(move |mut stream| {
-        Box::pin(async move {
-            let Some(auth) = cookies.get_private(AUTH_COOKIE_NAME) else {
-                let _ = stream.send(ws_err(ApplicationError::MissingAuthentication)).await;
-                return Ok(());
-            };
+) -> Result<ws::Channel<'a>, ApplicationError> {
+    let format = QuoteFormat::negotiate(format, broker.quote_format())?;
 
-            let mut credentials = Credentials::decode(auth.value()).unwrap();
+    let AuthenticatedUser {
+        session,
+        mut credentials,
+    } = user;
 
-            if credentials.is_expired() {
-                if let Err(e) = credentials.refresh_access_token(&oauth2).await {
-                    let _ = stream.send(ws_err(ApplicationError::InvalidCredentials(e))).await;
-                    return Ok(());
-                }
-            }
+    Ok(ws.channel(move |mut stream| {
+        Box::pin(async move {
+            let qm = qm.session(&session).await;
 
             qm.set_credentials(credentials.clone()).await;
 
             let mut subscription = qm.subscribe().await;
+            let mut ended = qm.ended();
 
-            let mut tickers = HashSet::new();
+            let hello = ServerBody::Hello {
+                protocol: PROTOCOL_VERSION,
+                session: SessionStatus::from(&credentials),
+            };
+            let _ = stream.send(hello.into()).await;
+
+            let mut warned_reauthentication = false;
+
+            // what to fall back on during an outage, which is only announced once
+            let mut last_good: Option<BrokerQuotes> = None;
+            let mut unavailable = false;
 
             loop {
                 select! {
@@ -159,25 +225,22 @@ pub async fn quotes_stream<'a, 'b: 'a>(
                         };
 
                         match incoming {
-                            Ok(Message::Text(txt)) => match serde_json::from_str::<ClientMsg>(&txt) {
-                                Ok(ClientMsg::Ping(pong)) => {
-                                    let _ = stream.send(Message::Pong(pong)).await;
-                                }
-                                Ok(ClientMsg::Add { symbols }) => {
-                                    tickers.extend(symbols);
-                                }
-                                Ok(ClientMsg::Remove { symbols }) => {
-                                    for e in &symbols {
-                                        tickers.remove(e);
+                            Ok(Message::Text(txt)) => {
+                                let reply = match ClientMessage::parse(&txt) {
+                                    Ok(ClientMessage { id, request, .. }) => {
+                                        match request {
+                                            ClientRequest::Add { symbols } => subscription.extend_unique(symbols).await,
+                                            ClientRequest::Remove { symbols } => subscription.remove(&symbols).await,
+                                            ClientRequest::Subscribe { symbols } => subscription.set_state(symbols).await,
+                                            ClientRequest::Ping => {}
+                                        }
+
+                                        ServerBody::Ack { id }
                                     }
-                                }
-                                Ok(ClientMsg::Subscribe { symbols }) => {
-                                    tickers.clear();
-                                    tickers.extend(symbols);
-                                }
-                                Err(_) => {
-                                    let _ = stream.send(ws_err(ApplicationError::InvalidWebSocketPayload)).await;
-                                }
+                                    Err(nack) => nack,
+                                };
+
+                                let _ = stream.send(reply.into()).await;
                             }
                             Ok(Message::Close(_)) => {
                                 break;
@@ -190,36 +253,113 @@ pub async fn quotes_stream<'a, 'b: 'a>(
                                 break;
                             }
                         }
+                    }
 
-                        qm.set_quotes(tickers.iter().cloned().collect()).await;
+                    _ = ended.changed() => {
+                        // logged out elsewhere
+                        let _ = stream.send(Message::Close(None)).await;
+                        break;
                     }
 
                     msg = subscription.recv() => {
-                        let Ok(message) = msg else {
-                            break;
+                        let message = match msg {
+                            Ok(message) => message,
+                            Err(RecvError::Lagged(skipped)) => {
+                                // too slow to keep up: skip the missed polls and send where things stand now
+                                warn!("quote stream fell {skipped} polls behind; resyncing");
+
+                                let snapshot = qm.fetch(subscription.states().await).await.and_then(|quotes| {
+                                    last_good = Some(quotes.clone());
+                                    format.apply(quotes)
+                                });
+
+                                let body = match snapshot {
+                                    Ok(quotes) => ServerBody::Snapshot { stale: false, resync: true, quotes },
+                                    Err(e) => ServerBody::Error(StreamError::from(&e)),
+                                };
+                                let _ = stream.send(body.into()).await;
+
+                                continue;
+                            }
+                            Err(RecvError::Closed) => break,
                         };
 
                         if credentials.is_expired() {
-                            credentials.refresh_access_token(&oauth2).await.unwrap();
+                            // another tab or request may have refreshed (and rotated the refresh token) already
+                            credentials = match sessions.load(&session).await {
+                                Ok(Some(stored)) => stored,
+                                Ok(None) => {
+                                    let error = StreamError::from(&ApplicationError::MissingAuthentication);
+                                    let _ = stream.send(ServerBody::Error(error).into()).await;
+                                    break;
+                                }
+                                Err(e) => {
+                                    let _ = stream.send(ServerBody::Error(StreamError::from(&e)).into()).await;
+                                    break;
+                                }
+                            };
+
+                            if credentials.is_expired() {
+                                if let Err(e) = credentials.refresh_access_token(&oauth2).await {
+                                    let error = StreamError::from(&ApplicationError::InvalidCredentials(e));
+                                    let _ = stream.send(ServerBody::Error(error).into()).await;
+                                    break;
+                                }
+
+                                // the refresh token may have rotated; losing it would leave the session dead
+                                if let Err(e) = sessions.save(&session, &credentials).await {
+                                    error!("could not persist refreshed credentials: {e}");
+                                    let _ = stream.send(ServerBody::Error(StreamError::from(&e)).into()).await;
+                                    break;
+                                }
+                            }
+
+                            qm.set_credentials(credentials.clone()).await;
                         }
 
-                        let response = message.map_err(ApplicationError::Polling);
+                        if !warned_reauthentication && credentials.is_refresh_token_expiring() {
+                            let status = SessionStatus::from(&credentials);
+                            let _ = stream.send(ServerBody::Status(StreamStatus::ReauthenticationRequired(status)).into()).await;
+                            warned_reauthentication = true;
+                        }
 
-                        let value = match response {
-                            Ok(message) => serde_json::to_string(&message).expect("Ok message should be serializable"),
-                            Err(message) => serde_json::to_string(&message).expect("Err message should be serializable"),
-                        };
+                        let body = match message {
+                            Ok(quotes) => {
+                                if unavailable {
+                                    unavailable = false;
+                                    let _ = stream.send(ServerBody::Status(StreamStatus::UpstreamRecovered).into()).await;
+                                }
+
+                                last_good = Some(quotes.clone());
+                                match format.apply(quotes) {
+                                    Ok(quotes) => ServerBody::Update { quotes },
+                                    Err(e) => ServerBody::Error(StreamError::from(&e)),
+                                }
+                            }
+                            Err(error) => {
+                                if let ApplicationError::UpstreamUnavailable { retry_after_ms } = *error {
+                                    if !unavailable {
+                                        unavailable = true;
+
+                                        let _ = stream.send(ServerBody::Status(StreamStatus::UpstreamUnavailable { retry_after_ms }).into()).await;
+                                        if let Some(Ok(quotes)) = last_good.clone().map(|quotes| format.apply(quotes)) {
+                                            let _ = stream.send(ServerBody::Snapshot { stale: true, resync: false, quotes }.into()).await;
+                                        }
+                                    }
 
-                        let _ = stream
-                            .send(Message::Text(value))
-                            .await;
+                                    continue;
+                                }
 
-                        qm.set_quotes(tickers.iter().cloned().collect()).await;
+                                ServerBody::Error(StreamError::from(&ApplicationError::Polling(error)))
+                            }
+                        };
+
+                        let _ = stream.send(body.into()).await;
                     }
                 }
             }
 
             Ok(())
         })
-    })
+    }))
 }
@@ -0,0 +1,228 @@
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use rocket::tokio::time::sleep;
+use serde::{Deserialize, Serialize};
+
+use crate::{errors::ApplicationError, schwab::client::Api};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bucket(capacity: u32, per_minute: u32, max_wait_ms: u64) -> TokenBucket {
+        TokenBucket::new(BucketConfig {
+            capacity,
+            per_minute,
+            max_wait_ms,
+        })
+    }
+
+    #[rocket::async_test]
+    async fn sheds_once_empty() {
+        let bucket = bucket(2, 60, 0);
+
+        assert!(bucket.acquire().await.is_ok());
+        assert!(bucket.acquire().await.is_ok());
+        assert!(bucket.status().available < 1.0);
+
+        let Err(ApplicationError::RateLimited { retry_after_ms }) = bucket.acquire().await else {
+            panic!("third request should be shed");
+        };
+        assert!(retry_after_ms > 900 && retry_after_ms <= 1000);
+    }
+
+    #[rocket::async_test]
+    async fn queues_within_max_wait() {
+        // ten tokens a second, so the third request waits about 100ms
+        let bucket = bucket(2, 600, 500);
+
+        let start = Instant::now();
+        for _ in 0..3 {
+            bucket.acquire().await.unwrap();
+        }
+
+        assert!(start.elapsed() >= Duration::from_millis(80));
+        assert!(bucket.pace() > Duration::ZERO);
+    }
+
+    #[rocket::async_test]
+    async fn refills_up_to_capacity() {
+        let bucket = bucket(1, 6000, 0);
+
+        bucket.acquire().await.unwrap();
+        sleep(Duration::from_millis(50)).await;
+
+        let status = bucket.status();
+        assert_eq!(status.available, 1.0);
+        assert_eq!(bucket.pace(), Duration::ZERO);
+    }
+}
+
+/// One token bucket's limits, read from `schwab.rate_limit.<family>`.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BucketConfig {
+    /// most requests that can go out back to back
+    pub capacity: u32,
+    /// sustained rate the bucket refills at
+    pub per_minute: u32,
+    /// how long a request may queue for a token before it is shed
+    pub max_wait_ms: u64,
+}
+
+impl Default for BucketConfig {
+    /// Schwab's documented quota is 120 requests a minute per app.
+    fn default() -> Self {
+        Self {
+            capacity: 20,
+            per_minute: 120,
+            max_wait_ms: 2000,
+        }
+    }
+}
+
+/// Limits for each endpoint family, read from the `schwab.rate_limit` config table.
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(default)]
+pub struct RateLimitConfig {
+    pub trader: BucketConfig,
+    pub market_data: BucketConfig,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    /// may go negative: each queued request reserves the token it is waiting for
+    tokens: f64,
+    updated: Instant,
+}
+
+/// A token bucket that queues requests for up to `max_wait_ms` and sheds the rest.
+#[derive(Debug)]
+pub struct TokenBucket {
+    config: BucketConfig,
+    bucket: Mutex<Bucket>,
+}
+
+impl TokenBucket {
+    /// A full bucket. A zero `capacity` or `per_minute` is treated as one.
+    pub fn new(mut config: BucketConfig) -> Self {
+        config.capacity = config.capacity.max(1);
+        config.per_minute = config.per_minute.max(1);
+
+        Self {
+            config,
+            bucket: Mutex::new(Bucket {
+                tokens: config.capacity.into(),
+                updated: Instant::now(),
+            }),
+        }
+    }
+
+    /// tokens per second
+    fn rate(&self) -> f64 {
+        f64::from(self.config.per_minute) / 60.0
+    }
+
+    /// Lock the bucket, topping it up for the time since it was last touched.
+    fn refilled(&self) -> std::sync::MutexGuard<'_, Bucket> {
+        let mut bucket = self.bucket.lock().expect("rate limiter lock poisoned");
+
+        let now = Instant::now();
+        let earned = now.duration_since(bucket.updated).as_secs_f64() * self.rate();
+        bucket.tokens = (bucket.tokens + earned).min(self.config.capacity.into());
+        bucket.updated = now;
+
+        bucket
+    }
+
+    /// Take a token, waiting for one if that is quicker than `max_wait_ms`.
+    pub async fn acquire(&self) -> Result<(), ApplicationError> {
+        let wait = {
+            let mut bucket = self.refilled();
+
+            let wait = Duration::from_secs_f64((1.0 - bucket.tokens).max(0.0) / self.rate());
+            if wait > Duration::from_millis(self.config.max_wait_ms) {
+                return Err(ApplicationError::RateLimited {
+                    retry_after_ms: wait.as_millis().try_into().unwrap_or(u64::MAX),
+                });
+            }
+
+            bucket.tokens -= 1.0;
+            wait
+        };
+
+        if !wait.is_zero() {
+            sleep(wait).await;
+        }
+
+        Ok(())
+    }
+
+    /// How long a regular poller should hold off on top of its usual delay: nothing
+    /// while the bucket is at least half full, otherwise until it is again.
+    pub fn pace(&self) -> Duration {
+        let bucket = self.refilled();
+        let half = f64::from(self.config.capacity) / 2.0;
+
+        if bucket.tokens >= half {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64((half - bucket.tokens) / self.rate())
+        }
+    }
+
+    pub fn status(&self) -> BucketStatus {
+        BucketStatus {
+            available: self.refilled().tokens.max(0.0),
+            config: self.config,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BucketStatus {
+    /// tokens left right now; fractional while refilling
+    pub available: f64,
+    #[serde(flatten)]
+    pub config: BucketConfig,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RateLimitStatus {
+    pub trader: BucketStatus,
+    pub market_data: BucketStatus,
+}
+
+/// Every Schwab request takes a token from its endpoint family's bucket, shared
+/// by all sessions since Schwab's quota is per app rather than per user.
+#[derive(Debug)]
+pub struct RateLimiter {
+    trader: TokenBucket,
+    market_data: TokenBucket,
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig) -> Self {
+        Self {
+            trader: TokenBucket::new(config.trader),
+            market_data: TokenBucket::new(config.market_data),
+        }
+    }
+
+    pub fn bucket(&self, api: Api) -> &TokenBucket {
+        match api {
+            Api::Trader => &self.trader,
+            Api::MarketData => &self.market_data,
+        }
+    }
+
+    pub fn status(&self) -> RateLimitStatus {
+        RateLimitStatus {
+            trader: self.trader.status(),
+            market_data: self.market_data.status(),
+        }
+    }
+}
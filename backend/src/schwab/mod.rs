@@ -1,108 +1,286 @@
+pub mod breaker;
+pub mod client;
+pub mod drift;
 pub mod endpoints;
+pub mod limiter;
+pub mod retry;
 pub mod schema;
 
-use base64::{Engine, prelude::BASE64_URL_SAFE};
-use itertools::Itertools;
-use reqwest::Client;
-use rocket_oauth2::OAuth2;
-use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use chrono::{TimeDelta, Utc};
+use rocket::futures::future::try_join_all;
 use serde_json::Value;
 
 use crate::{
+    broker::{
+        Account, Broker, BrokerQuotes, HistoryPeriod, Order, OrderSide, OrderStatus, OrderType,
+        PriceHistory, QuoteFormat,
+    },
     errors::ApplicationError,
-    oauth::{Credentials, Schwab},
-    schwab::schema::{QuoteResponse, SchwabAccount},
+    money::Money,
+    oauth::Credentials,
+    schwab::{
+        breaker::CircuitBreaker,
+        client::{Api, SchwabClient},
+        drift::{FieldDrift, SchemaDrift, from_value_with_unknowns},
+        limiter::RateLimitStatus,
+        schema::{AccountNumberHash, QuoteResponse, SchwabAccount, SchwabOrder},
+    },
+    symbol::Symbol,
 };
 
-const TRADER_API: &str = "https://api.schwabapi.com/trader/v1";
-const MARKET_DATA_API: &str = "https://api.schwabapi.com/marketdata/v1";
-
-#[derive(Clone, Debug, Serialize, Deserialize)]
-pub struct SchwabUsers(Vec<SchwabAccount>);
-
-pub async fn get_user(
-    credentials: &mut Credentials,
-    oauth2: &OAuth2<Schwab>,
-) -> Result<Vec<SchwabAccount>, ApplicationError> {
-    let client = Client::new();
-
-    let req = client
-        .get(format!("{TRADER_API}/userPreference"))
-        .header(
-            "Authorization",
-            format!(
-                "Bearer {}",
-                credentials
-                    .ensure_access_token(oauth2)
-                    .await
-                    .map_err(ApplicationError::InvalidCredentials)?
-            ),
-        )
-        .header("accept", "application/json");
-
-    let response = req
-        .send()
-        .await
-        .map_err(ApplicationError::Network)?
-        .text()
-        .await
-        .map_err(ApplicationError::Network)?;
-
-    let users: Value = serde_json::from_str(&response).map_err(ApplicationError::InvalidJson)?;
-    let accounts = users
-        .get("accounts")
-        .ok_or_else(|| ApplicationError::InvalidJsonLookup {
-            index: "accounts".to_owned(),
-            object: users.clone(),
-        })?;
-
-    assert!(accounts.is_array());
-
-    let mut result = vec![];
-
-    let accounts_as_array =
-        accounts
-            .as_array()
-            .ok_or_else(|| ApplicationError::InvalidJsonLookup {
-                index: "enforce<array>(accounts)".to_owned(),
-                object: users.clone(),
-            })?;
-
-    for account in accounts_as_array {
-        let account = serde_json::from_value::<SchwabAccount>(account.clone())
-            .map_err(ApplicationError::SchwabAccountDeserialization)?;
-        result.push(account);
-    }
+/// Most symbols Schwab accepts in one quotes request; longer lists are split up.
+const QUOTE_CHUNK_SIZE: usize = 500;
 
-    Ok(result)
+/// How far back order listings reach; Schwab requires an explicit window.
+const ORDER_LOOKBACK: TimeDelta = TimeDelta::days(60);
+
+/// The Schwab trader and market data APIs as a [`Broker`].
+#[derive(Debug)]
+pub struct SchwabBroker {
+    client: SchwabClient,
+    /// only with `schwab.strict_schema`
+    drift: Option<SchemaDrift>,
 }
 
-pub async fn get_quote(
-    credentials: Credentials,
-    client: Option<Client>,
-    quotes: Vec<String>,
-) -> Result<QuoteResponse, ApplicationError> {
-    let client = client.unwrap_or_default();
-
-    let symbols = quotes
-        .iter()
-        .map(|quote| BASE64_URL_SAFE.encode(quote))
-        .join(",");
-
-    let req = client.get(format!("{MARKET_DATA_API}/quotes?symbols={symbols}&fields=quote,fundamental,extended,reference,regular&indicative=false"))
-        .header("Authorization", format!("Bearer {}", credentials.access_token().ok_or(ApplicationError::MissingAuthentication)?))
-        .header("accept", "application/json");
-
-    let response = req
-        .send()
-        .await
-        .map_err(ApplicationError::Network)?
-        .text()
-        .await
-        .map_err(ApplicationError::Network)?;
-
-    let parsed = serde_json::from_str::<QuoteResponse>(&response)
+impl SchwabBroker {
+    pub fn new(client: SchwabClient) -> Self {
+        let drift = client.config().strict_schema.then(SchemaDrift::default);
+
+        Self { client, drift }
+    }
+
+    /// Quotes for at most [`QUOTE_CHUNK_SIZE`] symbols in one request.
+    async fn quote_chunk(
+        &self,
+        credentials: &Credentials,
+        symbols: &[Symbol],
+    ) -> Result<QuoteResponse, ApplicationError> {
+        let symbols: Vec<&str> = symbols.iter().map(Symbol::as_str).collect();
+
+        let req = self
+            .client
+            .get(Api::MarketData, "quotes", credentials)?
+            .query(&[
+                ("symbols", symbols.join(",").as_str()),
+                ("fields", "quote,fundamental,extended,reference,regular"),
+                ("indicative", "false"),
+            ]);
+
+        let text = req.text().await?;
+        let response = match &self.drift {
+            Some(_) => serde_json::from_str(&text).and_then(|raw| from_value_with_unknowns(&raw)),
+            None => serde_json::from_str::<QuoteResponse>(&text),
+        }
         .map_err(ApplicationError::QuoteResponseDeserialization)?;
 
-    Ok(parsed)
+        if let Some(drift) = &self.drift {
+            drift.record(&response);
+        }
+
+        Ok(response)
+    }
+
+    /// Trader endpoints address accounts by an encrypted hash rather than the account number.
+    async fn account_hash(
+        &self,
+        credentials: &Credentials,
+        account: &str,
+    ) -> Result<String, ApplicationError> {
+        let request = self
+            .client
+            .get(Api::Trader, "accounts/accountNumbers", credentials)?;
+
+        let hashes = serde_json::from_str::<Vec<AccountNumberHash>>(&request.text().await?)
+            .map_err(ApplicationError::InvalidJson)?;
+
+        hashes
+            .into_iter()
+            .find(|hash| hash.account_number == account)
+            .map(|hash| hash.hash_value)
+            .ok_or_else(|| ApplicationError::UnknownAccount(account.to_owned()))
+    }
+}
+
+#[rocket::async_trait]
+impl Broker for SchwabBroker {
+    async fn accounts(&self, credentials: &Credentials) -> Result<Vec<Account>, ApplicationError> {
+        let req = self
+            .client
+            .get(Api::Trader, "userPreference", credentials)?;
+
+        let response = req.text().await?;
+
+        let users: Value =
+            serde_json::from_str(&response).map_err(ApplicationError::InvalidJson)?;
+        let accounts =
+            users
+                .get("accounts")
+                .ok_or_else(|| ApplicationError::InvalidJsonLookup {
+                    index: "accounts".to_owned(),
+                    object: users.clone(),
+                })?;
+
+        let mut result = vec![];
+
+        let accounts_as_array =
+            accounts
+                .as_array()
+                .ok_or_else(|| ApplicationError::InvalidJsonLookup {
+                    index: "enforce<array>(accounts)".to_owned(),
+                    object: users.clone(),
+                })?;
+
+        for account in accounts_as_array {
+            let account = serde_json::from_value::<SchwabAccount>(account.clone())
+                .map_err(ApplicationError::SchwabAccountDeserialization)?;
+            result.push(account_from_schwab(account));
+        }
+
+        Ok(result)
+    }
+
+    async fn quotes(
+        &self,
+        credentials: &Credentials,
+        symbols: Vec<Symbol>,
+    ) -> Result<BrokerQuotes, ApplicationError> {
+        let chunks = symbols
+            .chunks(QUOTE_CHUNK_SIZE)
+            .map(|chunk| self.quote_chunk(credentials, chunk));
+
+        let mut merged = QuoteResponse::default();
+        for response in try_join_all(chunks).await? {
+            merged.merge(response);
+        }
+
+        Ok(merged.into())
+    }
+
+    async fn price_history(
+        &self,
+        credentials: &Credentials,
+        symbol: &str,
+        period: HistoryPeriod,
+    ) -> Result<PriceHistory, ApplicationError> {
+        let (period_type, period, frequency_type, frequency) = match period {
+            HistoryPeriod::Day => ("day", 1, "minute", 5),
+            HistoryPeriod::Week => ("day", 5, "minute", 30),
+            HistoryPeriod::Month => ("month", 1, "daily", 1),
+            HistoryPeriod::Year => ("year", 1, "daily", 1),
+        };
+
+        let req = self
+            .client
+            .get(Api::MarketData, "pricehistory", credentials)?
+            .query(&[
+                ("symbol", symbol),
+                ("periodType", period_type),
+                ("period", &period.to_string()),
+                ("frequencyType", frequency_type),
+                ("frequency", &frequency.to_string()),
+            ]);
+
+        serde_json::from_str(&req.text().await?).map_err(ApplicationError::InvalidJson)
+    }
+
+    async fn orders(
+        &self,
+        credentials: &Credentials,
+        account: &str,
+    ) -> Result<Vec<Order>, ApplicationError> {
+        let hash = self.account_hash(credentials, account).await?;
+
+        let now = Utc::now();
+        let format = "%Y-%m-%dT%H:%M:%S%.3fZ";
+
+        let req = self
+            .client
+            .get(Api::Trader, &format!("accounts/{hash}/orders"), credentials)?
+            .query(&[
+                (
+                    "fromEnteredTime",
+                    (now - ORDER_LOOKBACK).format(format).to_string(),
+                ),
+                ("toEnteredTime", now.format(format).to_string()),
+            ]);
+
+        let orders = serde_json::from_str::<Vec<SchwabOrder>>(&req.text().await?)
+            .map_err(ApplicationError::InvalidJson)?;
+
+        // orders we can't represent (stops, multi-leg strategies, ...) are left out
+        Ok(orders.into_iter().filter_map(order_from_schwab).collect())
+    }
+
+    fn quote_format(&self) -> QuoteFormat {
+        QuoteFormat::Schwab
+    }
+
+    fn rate_limits(&self) -> Option<RateLimitStatus> {
+        Some(self.client.limiter().status())
+    }
+
+    fn schema_drift(&self) -> Option<Vec<FieldDrift>> {
+        self.drift.as_ref().map(SchemaDrift::report)
+    }
+
+    fn quote_pace(&self) -> Duration {
+        let breaker = self
+            .client
+            .breaker(Api::MarketData)
+            .map_or(Duration::ZERO, CircuitBreaker::remaining);
+
+        self.client
+            .limiter()
+            .bucket(Api::MarketData)
+            .pace()
+            .max(breaker)
+    }
+}
+
+fn account_from_schwab(account: SchwabAccount) -> Account {
+    Account {
+        account_number: account.account_number,
+        nickname: account.nick_name,
+        primary: account.primary_account,
+        account_type: account.account_type,
+    }
+}
+
+fn order_from_schwab(order: SchwabOrder) -> Option<Order> {
+    let [leg] = order.order_leg_collection.as_slice() else {
+        return None;
+    };
+
+    let side = match leg.instruction.as_str() {
+        "BUY" | "BUY_TO_COVER" | "BUY_TO_OPEN" | "BUY_TO_CLOSE" => OrderSide::Buy,
+        "SELL" | "SELL_SHORT" | "SELL_TO_OPEN" | "SELL_TO_CLOSE" => OrderSide::Sell,
+        _ => return None,
+    };
+
+    let order_type = match order.order_type.as_str() {
+        "MARKET" => OrderType::Market,
+        "LIMIT" => OrderType::Limit,
+        _ => return None,
+    };
+
+    let status = match order.status.as_deref() {
+        Some("FILLED") => OrderStatus::Filled,
+        Some("CANCELED" | "EXPIRED" | "REPLACED") => OrderStatus::Canceled,
+        Some("REJECTED") => OrderStatus::Rejected,
+        _ => OrderStatus::Working,
+    };
+
+    Some(Order {
+        id: order.order_id?.to_string(),
+        symbol: leg.instrument.symbol.clone(),
+        side,
+        quantity: order.quantity,
+        filled_quantity: order.filled_quantity,
+        order_type,
+        limit_price: order.price.map(Money::to_f64),
+        status,
+        entered_time: order.entered_time,
+    })
 }
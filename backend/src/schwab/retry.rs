@@ -0,0 +1,147 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use rand::Rng;
+use reqwest::{
+    StatusCode,
+    header::{HeaderMap, RETRY_AFTER},
+};
+use serde::Deserialize;
+
+use crate::errors::ApplicationError;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_grows_within_bounds() {
+        let policy = RetryConfig {
+            max_attempts: 10,
+            base_delay_ms: 100,
+            max_delay_ms: 1000,
+            deadline_ms: 10_000,
+        };
+
+        for _ in 0..100 {
+            assert!(policy.backoff(1) <= Duration::from_millis(100));
+            assert!(policy.backoff(3) <= Duration::from_millis(400));
+            assert!(policy.backoff(30) <= Duration::from_millis(1000));
+        }
+    }
+
+    #[test]
+    fn parses_retry_after() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(retry_after(&headers), None);
+
+        headers.insert(RETRY_AFTER, "2".parse().unwrap());
+        assert_eq!(retry_after(&headers), Some(Duration::from_secs(2)));
+
+        let soon = (Utc::now() + chrono::TimeDelta::seconds(30)).to_rfc2822();
+        headers.insert(RETRY_AFTER, soon.parse().unwrap());
+        let wait = retry_after(&headers).unwrap();
+        assert!(wait > Duration::from_secs(25) && wait <= Duration::from_secs(30));
+
+        headers.insert(
+            RETRY_AFTER,
+            "Wed, 21 Oct 2015 07:28:00 GMT".parse().unwrap(),
+        );
+        assert_eq!(retry_after(&headers), Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn only_transient_failures_are_retried() {
+        assert!(is_retryable_status(StatusCode::TOO_MANY_REQUESTS));
+        assert!(is_retryable_status(StatusCode::SERVICE_UNAVAILABLE));
+        assert!(!is_retryable_status(StatusCode::UNAUTHORIZED));
+        assert!(!is_retryable_status(StatusCode::BAD_REQUEST));
+
+        assert!(!is_retryable(&ApplicationError::UpstreamUnauthorized));
+        assert!(!is_retryable(&ApplicationError::RateLimited {
+            retry_after_ms: 10
+        }));
+    }
+}
+
+/// How idempotent Schwab requests are retried, read from `schwab.retry`.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct RetryConfig {
+    /// attempts in total, including the first; 1 disables retries
+    pub max_attempts: u32,
+    /// upper bound of the first backoff, doubling with every retry
+    pub base_delay_ms: u64,
+    /// cap on any single backoff, though a longer `Retry-After` is still honored
+    pub max_delay_ms: u64,
+    /// time budget for a call, covering every attempt and the waits in between
+    pub deadline_ms: u64,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay_ms: 200,
+            max_delay_ms: 2000,
+            deadline_ms: 10_000,
+        }
+    }
+}
+
+impl RetryConfig {
+    pub fn deadline(&self) -> Duration {
+        Duration::from_millis(self.deadline_ms)
+    }
+
+    /// A random wait before retry number `retry` (from 1), up to an exponentially
+    /// growing bound so that clients failing together don't retry together.
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let bound = self
+            .base_delay_ms
+            .saturating_mul(1 << (retry.saturating_sub(1)).min(32))
+            .min(self.max_delay_ms);
+
+        Duration::from_millis(rand::thread_rng().gen_range(0..=bound))
+    }
+}
+
+/// Statuses that say "try again later" rather than "this request is wrong".
+pub fn is_retryable_status(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::TOO_MANY_REQUESTS
+            | StatusCode::INTERNAL_SERVER_ERROR
+            | StatusCode::BAD_GATEWAY
+            | StatusCode::SERVICE_UNAVAILABLE
+            | StatusCode::GATEWAY_TIMEOUT
+    )
+}
+
+/// Whether a failed attempt is worth repeating.
+pub fn is_retryable(error: &ApplicationError) -> bool {
+    match error {
+        ApplicationError::Network(e) => !e.is_builder() && !e.is_redirect(),
+        ApplicationError::UpstreamRateLimited => true,
+        ApplicationError::UpstreamFailed { status, .. } => {
+            StatusCode::from_u16(*status).is_ok_and(is_retryable_status)
+        }
+        _ => false,
+    }
+}
+
+/// The wait a `Retry-After` header asks for, given either as seconds or as an HTTP date.
+pub fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    let at = DateTime::parse_from_rfc2822(value).ok()?;
+    Some(
+        (at.with_timezone(&Utc) - Utc::now())
+            .to_std()
+            .unwrap_or_default(),
+    )
+}
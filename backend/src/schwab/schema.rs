@@ -1,6 +1,11 @@
-use serde::{Deserialize, Serialize};
+use chrono::{DateTime, NaiveDate, Utc, serde::ts_milliseconds_option};
+use schemars::JsonSchema;
+use serde::{Deserialize, Deserializer, Serialize, de::Error as _};
+use serde_json::Value;
 use std::collections::HashMap;
 
+use crate::{money::Money, schwab::drift::UnknownFields};
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -13,46 +18,171 @@ mod tests {
         assert!(result.is_ok())
     }
 
+    #[test]
+    fn merge_combines_invalid_symbols() {
+        let chunk = |json: &str| serde_json::from_str::<QuoteResponse>(json).unwrap();
+
+        let mut merged = chunk(r#"{"errors":{"invalidSymbols":["NOPE"]}}"#);
+        merged.merge(chunk(r#"{"errors":{"invalidSymbols":["ALSO"]}}"#));
+        merged.merge(chunk(r#"{}"#));
+
+        let Some(QuoteResponseObject::Error(errors)) = merged.quotes.get("errors") else {
+            panic!("errors should still be a QuoteError: {merged:?}");
+        };
+        assert_eq!(errors.invalid_symbols, ["NOPE", "ALSO"]);
+    }
+
+    #[test]
+    fn quotes_de_by_asset_type() {
+        let response = serde_json::from_str::<QuoteResponse>(
+            r#"{
+                "$SPX": {"assetMainType":"INDEX","ssid":1,"symbol":"$SPX","realtime":true,"quote":{"lastPrice":6000.0}},
+                "VFIAX": {"assetMainType":"MUTUAL_FUND","ssid":2,"symbol":"VFIAX","realtime":true,"quote":{"nAV":550.25}},
+                "errors": {"invalidSymbols":["NOPE"]}
+            }"#,
+        )
+        .unwrap();
+
+        assert!(matches!(
+            response.quotes["$SPX"],
+            QuoteResponseObject::Index(_)
+        ));
+        assert!(matches!(
+            response.quotes["VFIAX"],
+            QuoteResponseObject::MutualFund(_)
+        ));
+        assert!(matches!(
+            response.quotes["errors"],
+            QuoteResponseObject::Error(_)
+        ));
+    }
+
+    #[test]
+    fn typed_fields_keep_the_wire_format() {
+        let quote = serde_json::json!({
+            "askPrice": 200.01,
+            "bidPrice": 199.99,
+            "lastPrice": 200.5,
+            "quoteTime": 1_700_000_000_123_i64,
+            "tradeTime": 0,
+        });
+        let parsed = serde_json::from_value::<QuoteEquity>(quote.clone()).unwrap();
+
+        assert_eq!(
+            parsed.ask_price.unwrap() - parsed.bid_price.unwrap(),
+            "0.02".parse().unwrap()
+        );
+        assert_eq!(
+            parsed.quote_time.unwrap().timestamp_millis(),
+            1_700_000_000_123
+        );
+        assert_eq!(serde_json::to_value(&parsed).unwrap(), quote);
+
+        let fundamental = serde_json::json!({
+            "declarationDate": "2024-10-31T16:30:00Z",
+            "divExDate": "2024-11-08T00:00:00Z",
+            "divPayDate": "2024-11-14 00:00:00.0",
+            "nextDivExDate": "2025-02-10",
+        });
+        let parsed = serde_json::from_value::<Fundamental>(fundamental.clone()).unwrap();
+
+        let date = |date: &Option<SchwabDate>| date.as_ref().map(SchwabDate::date);
+        assert_eq!(
+            date(&parsed.declaration_date),
+            NaiveDate::from_ymd_opt(2024, 10, 31)
+        );
+        assert_eq!(
+            date(&parsed.div_ex_date),
+            NaiveDate::from_ymd_opt(2024, 11, 8)
+        );
+        assert_eq!(
+            date(&parsed.div_pay_date),
+            NaiveDate::from_ymd_opt(2024, 11, 14)
+        );
+        assert_eq!(
+            date(&parsed.next_div_ex_date),
+            NaiveDate::from_ymd_opt(2025, 2, 10)
+        );
+        // whatever the format, dates go back out as they came in
+        assert_eq!(serde_json::to_value(&parsed).unwrap(), fundamental);
+
+        assert!(
+            serde_json::from_value::<Fundamental>(serde_json::json!({ "divExDate": "soon" }))
+                .is_err()
+        );
+
+        let order = serde_json::from_str::<SchwabOrder>(
+            r#"{"orderType":"LIMIT","price":12.34,"quantity":1,"enteredTime":"2024-01-02T15:04:05+0000"}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            serde_json::to_value(&order).unwrap()["enteredTime"],
+            "2024-01-02T15:04:05+0000"
+        );
+    }
+
     #[test]
     fn full_error_de() {
         const JSON: &str = "{\"errors\":[{\"id\":\"a208a739-7d58-469a-b6c4-ec1395edb48f\",\"status\":\"400\",\"title\":\"Bad Request\",\"detail\":\"Search combination should have min of 1.\",\"source\":{\"pointer\":[\"/data/attributes/symbols\",\"/data/attributes/cusips\",\"/data/attributes/ssids\"]}}]}";
-        let result = serde_json::from_str::<QuoteResponse>(JSON);
+        let result = serde_json::from_str::<ApiErrorResponse>(JSON);
         dbg!(&result);
-        assert!(result.is_ok())
+        let result = result.unwrap();
+        assert_eq!(result.detail(), "Search combination should have min of 1.");
+        assert_eq!(result.pointers().len(), 3);
     }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct SchwabAccount {
     #[serde(rename(serialize = "accountNumber", deserialize = "accountNumber"))]
-    account_number: String,
+    pub account_number: String,
     #[serde(rename(serialize = "primaryAccount", deserialize = "primaryAccount"))]
-    primary_account: bool,
+    pub primary_account: bool,
     #[serde(rename(serialize = "type", deserialize = "type"))]
-    account_type: String,
+    pub account_type: String,
     #[serde(rename(serialize = "nickName", deserialize = "nickName"))]
-    nick_name: String,
+    pub nick_name: String,
     #[serde(rename(serialize = "displayAcctId", deserialize = "displayAcctId"))]
-    display_id: String,
+    pub display_id: String,
     #[serde(rename(serialize = "autoPositionEffect", deserialize = "autoPositionEffect"))]
-    auto_position_effect: bool,
+    pub auto_position_effect: bool,
     #[serde(rename(serialize = "accountColor", deserialize = "accountColor"))]
-    account_color: String,
+    pub account_color: String,
     #[serde(rename(serialize = "lotSelectionMethod", deserialize = "lotSelectionMethod"))]
-    lot_selection_method: String,
+    pub lot_selection_method: String,
     #[serde(rename(serialize = "hasFuturesAccount", deserialize = "hasFuturesAccount"))]
-    has_futures_account: bool,
+    pub has_futures_account: bool,
     #[serde(rename(serialize = "hasForexAccount", deserialize = "hasForexAccount"))]
-    has_forex_account: bool,
+    pub has_forex_account: bool,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
 pub struct QuoteResponse {
     #[serde(flatten)]
     pub quotes: HashMap<String, QuoteResponseObject>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl QuoteResponse {
+    /// Fold in another response, e.g. for a different chunk of symbols.
+    pub fn merge(&mut self, other: QuoteResponse) {
+        for (key, quote) in other.quotes {
+            match (self.quotes.get_mut(&key), quote) {
+                (Some(QuoteResponseObject::Error(errors)), QuoteResponseObject::Error(more)) => {
+                    errors.invalid_cusips.extend(more.invalid_cusips);
+                    errors.invalid_ssids.extend(more.invalid_ssids);
+                    errors.invalid_symbols.extend(more.invalid_symbols);
+                }
+                (_, quote) => {
+                    self.quotes.insert(key, quote);
+                }
+            }
+        }
+    }
+}
+
+/// Serialized as is; deserialized by `assetMainType`, since every quote block is
+/// optional and an untagged match would read anything as an equity.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
 #[serde(untagged)]
 #[allow(clippy::large_enum_variant)]
 pub enum QuoteResponseObject {
@@ -64,11 +194,41 @@ pub enum QuoteResponseObject {
     Index(IndexResponse),
     MutualFund(MutualFundResponse),
     Error(QuoteError),
-    ApiError(ApiErrorResponse),
+}
+
+impl<'de> Deserialize<'de> for QuoteResponseObject {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = Value::deserialize(deserializer)?;
+
+        let asset_type = match value.get("assetMainType") {
+            Some(asset_type) => {
+                Some(AssetMainType::deserialize(asset_type).map_err(D::Error::custom)?)
+            }
+            None => None,
+        };
+
+        let object = match asset_type {
+            None => serde_json::from_value(value).map(Self::Error),
+            Some(AssetMainType::Option) => serde_json::from_value(value).map(Self::Option),
+            Some(AssetMainType::Forex) => serde_json::from_value(value).map(Self::Forex),
+            Some(AssetMainType::Future) => serde_json::from_value(value).map(Self::Future),
+            Some(AssetMainType::FutureOption) => {
+                serde_json::from_value(value).map(Self::FutureOption)
+            }
+            Some(AssetMainType::Index) => serde_json::from_value(value).map(Self::Index),
+            Some(AssetMainType::MutualFund) => serde_json::from_value(value).map(Self::MutualFund),
+            // there is no bond response type, so bonds are read like equities
+            Some(AssetMainType::Equity | AssetMainType::Bond) => {
+                serde_json::from_value(value).map(Self::Equity)
+            }
+        };
+
+        object.map_err(D::Error::custom)
+    }
 }
 
 // Asset Types
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum AssetMainType {
     Bond,
@@ -81,7 +241,7 @@ pub enum AssetMainType {
     Option,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum EquityAssetSubType {
     Coe,
@@ -96,7 +256,7 @@ pub enum EquityAssetSubType {
     Rgt,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum MutualFundAssetSubType {
     Oef,
@@ -104,7 +264,7 @@ pub enum MutualFundAssetSubType {
     Mmf,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum QuoteType {
     Nbbo,
@@ -112,7 +272,7 @@ pub enum QuoteType {
 }
 
 // Equity Response
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct EquityResponse {
     pub asset_main_type: AssetMainType,
@@ -133,89 +293,131 @@ pub struct EquityResponse {
     pub reference: Option<ReferenceEquity>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub regular: Option<RegularMarket>,
+    /// keys Schwab sent that this type doesn't know about
+    #[serde(flatten, skip_serializing_if = "UnknownFields::is_empty")]
+    pub unknown: UnknownFields,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct ExtendedMarket {
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub ask_price: Option<f64>,
+    pub ask_price: Option<Money>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub ask_size: Option<i32>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub bid_price: Option<f64>,
+    pub bid_price: Option<Money>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub bid_size: Option<i32>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub last_price: Option<f64>,
+    pub last_price: Option<Money>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub last_size: Option<i32>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub mark: Option<f64>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub quote_time: Option<i64>,
+    pub mark: Option<Money>,
+    #[serde(
+        default,
+        with = "ts_milliseconds_option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    #[schemars(with = "Option<i64>")]
+    pub quote_time: Option<DateTime<Utc>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub total_volume: Option<i64>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub trade_time: Option<i64>,
+    #[serde(
+        default,
+        with = "ts_milliseconds_option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    #[schemars(with = "Option<i64>")]
+    pub trade_time: Option<DateTime<Utc>>,
+    /// keys Schwab sent that this type doesn't know about
+    #[serde(flatten, skip_serializing_if = "UnknownFields::is_empty")]
+    pub unknown: UnknownFields,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct Fundamental {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub avg10_days_volume: Option<f64>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub avg1_year_volume: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(with = "Option<String>")]
+    pub declaration_date: Option<SchwabDate>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub declaration_date: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub div_amount: Option<f64>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub div_ex_date: Option<String>,
+    pub div_amount: Option<Money>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(with = "Option<String>")]
+    pub div_ex_date: Option<SchwabDate>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub div_freq: Option<DivFreq>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub div_pay_amount: Option<f64>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub div_pay_date: Option<String>,
+    pub div_pay_amount: Option<Money>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(with = "Option<String>")]
+    pub div_pay_date: Option<SchwabDate>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub div_yield: Option<f64>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub eps: Option<f64>,
+    pub eps: Option<Money>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub fund_leverage_factor: Option<f64>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub fund_strategy: Option<FundStrategy>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub next_div_ex_date: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub next_div_pay_date: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(with = "Option<String>")]
+    pub next_div_ex_date: Option<SchwabDate>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(with = "Option<String>")]
+    pub next_div_pay_date: Option<SchwabDate>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub pe_ratio: Option<f64>,
+    /// keys Schwab sent that this type doesn't know about
+    #[serde(flatten, skip_serializing_if = "UnknownFields::is_empty")]
+    pub unknown: UnknownFields,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Dividends per year, sent as a plain number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(try_from = "u8", into = "u8")]
 pub enum DivFreq {
-    #[serde(rename = "1")]
-    Annually,
-    #[serde(rename = "2")]
-    SemiAnnually,
-    #[serde(rename = "3")]
-    Triannually,
-    #[serde(rename = "4")]
-    Quarterly,
-    #[serde(rename = "6")]
-    Bimonthly,
-    #[serde(rename = "11")]
-    ElevenTimes,
-    #[serde(rename = "12")]
-    Monthly,
-    #[serde(rename = "0")]
-    None,
+    Annually = 1,
+    SemiAnnually = 2,
+    Triannually = 3,
+    Quarterly = 4,
+    Bimonthly = 6,
+    ElevenTimes = 11,
+    Monthly = 12,
+    None = 0,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl TryFrom<u8> for DivFreq {
+    type Error = String;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Ok(match value {
+            1 => Self::Annually,
+            2 => Self::SemiAnnually,
+            3 => Self::Triannually,
+            4 => Self::Quarterly,
+            6 => Self::Bimonthly,
+            11 => Self::ElevenTimes,
+            12 => Self::Monthly,
+            0 => Self::None,
+            _ => return Err(format!("{value} is not a dividend frequency")),
+        })
+    }
+}
+
+impl From<DivFreq> for u8 {
+    fn from(value: DivFreq) -> Self {
+        value as u8
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub enum FundStrategy {
     A, // Active
     L, // Leveraged
@@ -224,66 +426,89 @@ pub enum FundStrategy {
     S, // Short
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct QuoteEquity {
     #[serde(rename = "52WeekHigh", skip_serializing_if = "Option::is_none")]
-    pub week_52_high: Option<f64>,
+    pub week_52_high: Option<Money>,
     #[serde(rename = "52WeekLow", skip_serializing_if = "Option::is_none")]
-    pub week_52_low: Option<f64>,
+    pub week_52_low: Option<Money>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub ask_mic_id: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub ask_price: Option<f64>,
+    pub ask_price: Option<Money>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub ask_size: Option<i32>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub ask_time: Option<i64>,
+    #[serde(
+        default,
+        with = "ts_milliseconds_option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    #[schemars(with = "Option<i64>")]
+    pub ask_time: Option<DateTime<Utc>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub bid_mic_id: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub bid_price: Option<f64>,
+    pub bid_price: Option<Money>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub bid_size: Option<i32>,
+    #[serde(
+        default,
+        with = "ts_milliseconds_option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    #[schemars(with = "Option<i64>")]
+    pub bid_time: Option<DateTime<Utc>>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub bid_time: Option<i64>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub close_price: Option<f64>,
+    pub close_price: Option<Money>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub high_price: Option<f64>,
+    pub high_price: Option<Money>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub last_mic_id: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub last_price: Option<f64>,
+    pub last_price: Option<Money>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub last_size: Option<i32>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub low_price: Option<f64>,
+    pub low_price: Option<Money>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub mark: Option<f64>,
+    pub mark: Option<Money>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub mark_change: Option<f64>,
+    pub mark_change: Option<Money>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub mark_percent_change: Option<f64>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub net_change: Option<f64>,
+    pub net_change: Option<Money>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub net_percent_change: Option<f64>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub open_price: Option<f64>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub quote_time: Option<i64>,
+    pub open_price: Option<Money>,
+    #[serde(
+        default,
+        with = "ts_milliseconds_option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    #[schemars(with = "Option<i64>")]
+    pub quote_time: Option<DateTime<Utc>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub security_status: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub total_volume: Option<i64>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub trade_time: Option<i64>,
+    #[serde(
+        default,
+        with = "ts_milliseconds_option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    #[schemars(with = "Option<i64>")]
+    pub trade_time: Option<DateTime<Utc>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub volatility: Option<f64>,
+    /// keys Schwab sent that this type doesn't know about
+    #[serde(flatten, skip_serializing_if = "UnknownFields::is_empty")]
+    pub unknown: UnknownFields,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct ReferenceEquity {
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -306,25 +531,36 @@ pub struct ReferenceEquity {
     pub is_shortable: Option<bool>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub otc_market_tier: Option<String>,
+    /// keys Schwab sent that this type doesn't know about
+    #[serde(flatten, skip_serializing_if = "UnknownFields::is_empty")]
+    pub unknown: UnknownFields,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct RegularMarket {
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub regular_market_last_price: Option<f64>,
+    pub regular_market_last_price: Option<Money>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub regular_market_last_size: Option<i32>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub regular_market_net_change: Option<f64>,
+    pub regular_market_net_change: Option<Money>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub regular_market_percent_change: Option<f64>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub regular_market_trade_time: Option<i64>,
+    #[serde(
+        default,
+        with = "ts_milliseconds_option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    #[schemars(with = "Option<i64>")]
+    pub regular_market_trade_time: Option<DateTime<Utc>>,
+    /// keys Schwab sent that this type doesn't know about
+    #[serde(flatten, skip_serializing_if = "UnknownFields::is_empty")]
+    pub unknown: UnknownFields,
 }
 
 // Option Response
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct OptionResponse {
     pub asset_main_type: AssetMainType,
@@ -335,98 +571,119 @@ pub struct OptionResponse {
     pub quote: Option<QuoteOption>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub reference: Option<ReferenceOption>,
+    /// keys Schwab sent that this type doesn't know about
+    #[serde(flatten, skip_serializing_if = "UnknownFields::is_empty")]
+    pub unknown: UnknownFields,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct QuoteOption {
     #[serde(rename = "52WeekHigh", skip_serializing_if = "Option::is_none")]
-    pub week_52_high: Option<f64>,
+    pub week_52_high: Option<Money>,
     #[serde(rename = "52WeekLow", skip_serializing_if = "Option::is_none")]
-    pub week_52_low: Option<f64>,
+    pub week_52_low: Option<Money>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub ask_price: Option<f64>,
+    pub ask_price: Option<Money>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub ask_size: Option<i32>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub bid_price: Option<f64>,
+    pub bid_price: Option<Money>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub bid_size: Option<i32>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub close_price: Option<f64>,
+    pub close_price: Option<Money>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub delta: Option<f64>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub gamma: Option<f64>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub high_price: Option<f64>,
+    pub high_price: Option<Money>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub ind_ask_price: Option<f64>,
+    pub ind_ask_price: Option<Money>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub ind_bid_price: Option<f64>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub ind_quote_time: Option<i64>,
+    pub ind_bid_price: Option<Money>,
+    #[serde(
+        default,
+        with = "ts_milliseconds_option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    #[schemars(with = "Option<i64>")]
+    pub ind_quote_time: Option<DateTime<Utc>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub implied_yield: Option<f64>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub last_price: Option<f64>,
+    pub last_price: Option<Money>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub last_size: Option<i32>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub low_price: Option<f64>,
+    pub low_price: Option<Money>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub mark: Option<f64>,
+    pub mark: Option<Money>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub mark_change: Option<f64>,
+    pub mark_change: Option<Money>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub mark_percent_change: Option<f64>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub money_intrinsic_value: Option<f64>,
+    pub money_intrinsic_value: Option<Money>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub net_change: Option<f64>,
+    pub net_change: Option<Money>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub net_percent_change: Option<f64>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub open_interest: Option<f64>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub open_price: Option<f64>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub quote_time: Option<i64>,
+    pub open_price: Option<Money>,
+    #[serde(
+        default,
+        with = "ts_milliseconds_option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    #[schemars(with = "Option<i64>")]
+    pub quote_time: Option<DateTime<Utc>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub rho: Option<f64>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub security_status: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub theoretical_option_value: Option<f64>,
+    pub theoretical_option_value: Option<Money>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub theta: Option<f64>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub time_value: Option<f64>,
+    pub time_value: Option<Money>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub total_volume: Option<i64>,
+    #[serde(
+        default,
+        with = "ts_milliseconds_option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    #[schemars(with = "Option<i64>")]
+    pub trade_time: Option<DateTime<Utc>>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub trade_time: Option<i64>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub underlying_price: Option<f64>,
+    pub underlying_price: Option<Money>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub vega: Option<f64>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub volatility: Option<f64>,
+    /// keys Schwab sent that this type doesn't know about
+    #[serde(flatten, skip_serializing_if = "UnknownFields::is_empty")]
+    pub unknown: UnknownFields,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub enum ContractType {
     P, // Put
     C, // Call
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub enum ExerciseType {
     A, // American
     E, // European
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub enum ExpirationType {
     M, // End of Month
     Q, // Quarterly
@@ -434,13 +691,13 @@ pub enum ExpirationType {
     S, // Standard (3rd Friday)
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub enum SettlementType {
     A, // AM
     P, // PM
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct ReferenceOption {
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -469,20 +726,28 @@ pub struct ReferenceOption {
     pub expiration_year: Option<i32>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub is_penny_pilot: Option<bool>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub last_trading_day: Option<i64>,
+    #[serde(
+        default,
+        with = "ts_milliseconds_option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    #[schemars(with = "Option<i64>")]
+    pub last_trading_day: Option<DateTime<Utc>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub multiplier: Option<f64>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub settlement_type: Option<SettlementType>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub strike_price: Option<f64>,
+    pub strike_price: Option<Money>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub underlying: Option<String>,
+    /// keys Schwab sent that this type doesn't know about
+    #[serde(flatten, skip_serializing_if = "UnknownFields::is_empty")]
+    pub unknown: UnknownFields,
 }
 
 // Forex Response
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct ForexResponse {
     pub asset_main_type: AssetMainType,
@@ -493,56 +758,72 @@ pub struct ForexResponse {
     pub quote: Option<QuoteForex>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub reference: Option<ReferenceForex>,
+    /// keys Schwab sent that this type doesn't know about
+    #[serde(flatten, skip_serializing_if = "UnknownFields::is_empty")]
+    pub unknown: UnknownFields,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct QuoteForex {
     #[serde(rename = "52WeekHigh", skip_serializing_if = "Option::is_none")]
-    pub week_52_high: Option<f64>,
+    pub week_52_high: Option<Money>,
     #[serde(rename = "52WeekLow", skip_serializing_if = "Option::is_none")]
-    pub week_52_low: Option<f64>,
+    pub week_52_low: Option<Money>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub ask_price: Option<f64>,
+    pub ask_price: Option<Money>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub ask_size: Option<i32>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub bid_price: Option<f64>,
+    pub bid_price: Option<Money>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub bid_size: Option<i32>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub close_price: Option<f64>,
+    pub close_price: Option<Money>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub high_price: Option<f64>,
+    pub high_price: Option<Money>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub last_price: Option<f64>,
+    pub last_price: Option<Money>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub last_size: Option<i32>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub low_price: Option<f64>,
+    pub low_price: Option<Money>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub mark: Option<f64>,
+    pub mark: Option<Money>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub net_change: Option<f64>,
+    pub net_change: Option<Money>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub net_percent_change: Option<f64>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub open_price: Option<f64>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub quote_time: Option<i64>,
+    pub open_price: Option<Money>,
+    #[serde(
+        default,
+        with = "ts_milliseconds_option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    #[schemars(with = "Option<i64>")]
+    pub quote_time: Option<DateTime<Utc>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub security_status: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub tick: Option<f64>,
+    pub tick: Option<Money>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub tick_amount: Option<f64>,
+    pub tick_amount: Option<Money>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub total_volume: Option<i64>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub trade_time: Option<i64>,
+    #[serde(
+        default,
+        with = "ts_milliseconds_option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    #[schemars(with = "Option<i64>")]
+    pub trade_time: Option<DateTime<Utc>>,
+    /// keys Schwab sent that this type doesn't know about
+    #[serde(flatten, skip_serializing_if = "UnknownFields::is_empty")]
+    pub unknown: UnknownFields,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct ReferenceForex {
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -559,10 +840,13 @@ pub struct ReferenceForex {
     pub product: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub trading_hours: Option<String>,
+    /// keys Schwab sent that this type doesn't know about
+    #[serde(flatten, skip_serializing_if = "UnknownFields::is_empty")]
+    pub unknown: UnknownFields,
 }
 
 // Future Response
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct FutureResponse {
     pub asset_main_type: AssetMainType,
@@ -573,68 +857,99 @@ pub struct FutureResponse {
     pub quote: Option<QuoteFuture>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub reference: Option<ReferenceFuture>,
+    /// keys Schwab sent that this type doesn't know about
+    #[serde(flatten, skip_serializing_if = "UnknownFields::is_empty")]
+    pub unknown: UnknownFields,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct QuoteFuture {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub ask_mic_id: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub ask_price: Option<f64>,
+    pub ask_price: Option<Money>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub ask_size: Option<i32>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub ask_time: Option<i64>,
+    #[serde(
+        default,
+        with = "ts_milliseconds_option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    #[schemars(with = "Option<i64>")]
+    pub ask_time: Option<DateTime<Utc>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub bid_mic_id: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub bid_price: Option<f64>,
+    pub bid_price: Option<Money>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub bid_size: Option<i32>,
+    #[serde(
+        default,
+        with = "ts_milliseconds_option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    #[schemars(with = "Option<i64>")]
+    pub bid_time: Option<DateTime<Utc>>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub bid_time: Option<i64>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub close_price: Option<f64>,
+    pub close_price: Option<Money>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub future_percent_change: Option<f64>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub high_price: Option<f64>,
+    pub high_price: Option<Money>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub last_mic_id: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub last_price: Option<f64>,
+    pub last_price: Option<Money>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub last_size: Option<i32>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub low_price: Option<f64>,
+    pub low_price: Option<Money>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub mark: Option<f64>,
+    pub mark: Option<Money>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub net_change: Option<f64>,
+    pub net_change: Option<Money>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub open_interest: Option<i32>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub open_price: Option<f64>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub quote_time: Option<i64>,
+    pub open_price: Option<Money>,
+    #[serde(
+        default,
+        with = "ts_milliseconds_option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    #[schemars(with = "Option<i64>")]
+    pub quote_time: Option<DateTime<Utc>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub quoted_in_session: Option<bool>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub security_status: Option<String>,
+    #[serde(
+        default,
+        with = "ts_milliseconds_option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    #[schemars(with = "Option<i64>")]
+    pub settle_time: Option<DateTime<Utc>>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub settle_time: Option<i64>,
+    pub tick: Option<Money>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub tick: Option<f64>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub tick_amount: Option<f64>,
+    pub tick_amount: Option<Money>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub total_volume: Option<i64>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub trade_time: Option<i64>,
+    #[serde(
+        default,
+        with = "ts_milliseconds_option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    #[schemars(with = "Option<i64>")]
+    pub trade_time: Option<DateTime<Utc>>,
+    /// keys Schwab sent that this type doesn't know about
+    #[serde(flatten, skip_serializing_if = "UnknownFields::is_empty")]
+    pub unknown: UnknownFields,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct ReferenceFuture {
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -645,8 +960,13 @@ pub struct ReferenceFuture {
     pub exchange_name: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub future_active_symbol: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub future_expiration_date: Option<i64>,
+    #[serde(
+        default,
+        with = "ts_milliseconds_option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    #[schemars(with = "Option<i64>")]
+    pub future_expiration_date: Option<DateTime<Utc>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub future_is_active: Option<bool>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -654,15 +974,18 @@ pub struct ReferenceFuture {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub future_price_format: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub future_settlement_price: Option<f64>,
+    pub future_settlement_price: Option<Money>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub future_trading_hours: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub product: Option<String>,
+    /// keys Schwab sent that this type doesn't know about
+    #[serde(flatten, skip_serializing_if = "UnknownFields::is_empty")]
+    pub unknown: UnknownFields,
 }
 
 // Future Option Response
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct FutureOptionResponse {
     pub asset_main_type: AssetMainType,
@@ -673,64 +996,80 @@ pub struct FutureOptionResponse {
     pub quote: Option<QuoteFutureOption>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub reference: Option<ReferenceFutureOption>,
+    /// keys Schwab sent that this type doesn't know about
+    #[serde(flatten, skip_serializing_if = "UnknownFields::is_empty")]
+    pub unknown: UnknownFields,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct QuoteFutureOption {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub ask_mic_id: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub ask_price: Option<f64>,
+    pub ask_price: Option<Money>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub ask_size: Option<i32>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub bid_mic_id: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub bid_price: Option<f64>,
+    pub bid_price: Option<Money>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub bid_size: Option<i32>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub close_price: Option<f64>,
+    pub close_price: Option<Money>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub high_price: Option<f64>,
+    pub high_price: Option<Money>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub last_mic_id: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub last_price: Option<f64>,
+    pub last_price: Option<Money>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub last_size: Option<i32>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub low_price: Option<f64>,
+    pub low_price: Option<Money>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub mark: Option<f64>,
+    pub mark: Option<Money>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub mark_change: Option<f64>,
+    pub mark_change: Option<Money>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub net_change: Option<f64>,
+    pub net_change: Option<Money>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub net_percent_change: Option<f64>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub open_interest: Option<i32>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub open_price: Option<f64>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub quote_time: Option<i64>,
+    pub open_price: Option<Money>,
+    #[serde(
+        default,
+        with = "ts_milliseconds_option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    #[schemars(with = "Option<i64>")]
+    pub quote_time: Option<DateTime<Utc>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub security_status: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub settlemet_price: Option<f64>,
+    pub settlemet_price: Option<Money>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub tick: Option<f64>,
+    pub tick: Option<Money>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub tick_amount: Option<f64>,
+    pub tick_amount: Option<Money>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub total_volume: Option<i64>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub trade_time: Option<i64>,
+    #[serde(
+        default,
+        with = "ts_milliseconds_option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    #[schemars(with = "Option<i64>")]
+    pub trade_time: Option<DateTime<Utc>>,
+    /// keys Schwab sent that this type doesn't know about
+    #[serde(flatten, skip_serializing_if = "UnknownFields::is_empty")]
+    pub unknown: UnknownFields,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct ReferenceFutureOption {
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -743,18 +1082,26 @@ pub struct ReferenceFutureOption {
     pub exchange_name: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub multiplier: Option<f64>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub expiration_date: Option<i64>,
+    #[serde(
+        default,
+        with = "ts_milliseconds_option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    #[schemars(with = "Option<i64>")]
+    pub expiration_date: Option<DateTime<Utc>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub expiration_style: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub strike_price: Option<f64>,
+    pub strike_price: Option<Money>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub underlying: Option<String>,
+    /// keys Schwab sent that this type doesn't know about
+    #[serde(flatten, skip_serializing_if = "UnknownFields::is_empty")]
+    pub unknown: UnknownFields,
 }
 
 // Index Response
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct IndexResponse {
     pub asset_main_type: AssetMainType,
@@ -765,38 +1112,49 @@ pub struct IndexResponse {
     pub quote: Option<QuoteIndex>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub reference: Option<ReferenceIndex>,
+    /// keys Schwab sent that this type doesn't know about
+    #[serde(flatten, skip_serializing_if = "UnknownFields::is_empty")]
+    pub unknown: UnknownFields,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct QuoteIndex {
     #[serde(rename = "52WeekHigh", skip_serializing_if = "Option::is_none")]
-    pub week_52_high: Option<f64>,
+    pub week_52_high: Option<Money>,
     #[serde(rename = "52WeekLow", skip_serializing_if = "Option::is_none")]
-    pub week_52_low: Option<f64>,
+    pub week_52_low: Option<Money>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub close_price: Option<f64>,
+    pub close_price: Option<Money>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub high_price: Option<f64>,
+    pub high_price: Option<Money>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub last_price: Option<f64>,
+    pub last_price: Option<Money>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub low_price: Option<f64>,
+    pub low_price: Option<Money>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub net_change: Option<f64>,
+    pub net_change: Option<Money>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub net_percent_change: Option<f64>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub open_price: Option<f64>,
+    pub open_price: Option<Money>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub security_status: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub total_volume: Option<i64>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub trade_time: Option<i64>,
+    #[serde(
+        default,
+        with = "ts_milliseconds_option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    #[schemars(with = "Option<i64>")]
+    pub trade_time: Option<DateTime<Utc>>,
+    /// keys Schwab sent that this type doesn't know about
+    #[serde(flatten, skip_serializing_if = "UnknownFields::is_empty")]
+    pub unknown: UnknownFields,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct ReferenceIndex {
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -805,10 +1163,13 @@ pub struct ReferenceIndex {
     pub exchange: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub exchange_name: Option<String>,
+    /// keys Schwab sent that this type doesn't know about
+    #[serde(flatten, skip_serializing_if = "UnknownFields::is_empty")]
+    pub unknown: UnknownFields,
 }
 
 // Mutual Fund Response
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct MutualFundResponse {
     pub asset_main_type: AssetMainType,
@@ -823,32 +1184,43 @@ pub struct MutualFundResponse {
     pub quote: Option<QuoteMutualFund>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub reference: Option<ReferenceMutualFund>,
+    /// keys Schwab sent that this type doesn't know about
+    #[serde(flatten, skip_serializing_if = "UnknownFields::is_empty")]
+    pub unknown: UnknownFields,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct QuoteMutualFund {
     #[serde(rename = "52WeekHigh", skip_serializing_if = "Option::is_none")]
-    pub week_52_high: Option<f64>,
+    pub week_52_high: Option<Money>,
     #[serde(rename = "52WeekLow", skip_serializing_if = "Option::is_none")]
-    pub week_52_low: Option<f64>,
+    pub week_52_low: Option<Money>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub close_price: Option<f64>,
+    pub close_price: Option<Money>,
     #[serde(rename = "nAV", skip_serializing_if = "Option::is_none")]
-    pub nav: Option<f64>,
+    pub nav: Option<Money>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub net_change: Option<f64>,
+    pub net_change: Option<Money>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub net_percent_change: Option<f64>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub security_status: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub total_volume: Option<i64>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub trade_time: Option<i64>,
+    #[serde(
+        default,
+        with = "ts_milliseconds_option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    #[schemars(with = "Option<i64>")]
+    pub trade_time: Option<DateTime<Utc>>,
+    /// keys Schwab sent that this type doesn't know about
+    #[serde(flatten, skip_serializing_if = "UnknownFields::is_empty")]
+    pub unknown: UnknownFields,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct ReferenceMutualFund {
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -859,23 +1231,55 @@ pub struct ReferenceMutualFund {
     pub exchange: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub exchange_name: Option<String>,
+    /// keys Schwab sent that this type doesn't know about
+    #[serde(flatten, skip_serializing_if = "UnknownFields::is_empty")]
+    pub unknown: UnknownFields,
 }
 
 // Error Response
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct QuoteError {
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub invalid_cusips: Vec<String>,
-    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[serde(
+        rename = "invalidSSIDs",
+        default,
+        skip_serializing_if = "Vec::is_empty"
+    )]
     pub invalid_ssids: Vec<i64>,
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub invalid_symbols: Vec<String>,
+    /// keys Schwab sent that this type doesn't know about
+    #[serde(flatten, skip_serializing_if = "UnknownFields::is_empty")]
+    pub unknown: UnknownFields,
 }
 
 // API Error Response (for HTTP error responses)
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ApiErrorResponse(pub Vec<ApiError>);
+pub struct ApiErrorResponse {
+    pub errors: Vec<ApiError>,
+}
+
+impl ApiErrorResponse {
+    /// Every error's detail, falling back to its title.
+    pub fn detail(&self) -> String {
+        self.errors
+            .iter()
+            .filter_map(|error| error.detail.as_deref().or(error.title.as_deref()))
+            .collect::<Vec<_>>()
+            .join("; ")
+    }
+
+    /// Every field the errors point at, e.g. `/data/attributes/symbols`.
+    pub fn pointers(&self) -> Vec<String> {
+        self.errors
+            .iter()
+            .filter_map(|error| error.source.as_ref()?.pointer.clone())
+            .flatten()
+            .collect()
+    }
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ApiError {
@@ -896,3 +1300,119 @@ pub struct ErrorSource {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub pointer: Option<Vec<String>>,
 }
+
+// Accounts
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountNumberHash {
+    pub account_number: String,
+    /// The encrypted account id the trader API expects in URLs.
+    pub hash_value: String,
+}
+
+// Orders
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SchwabOrder {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub order_id: Option<i64>,
+    pub order_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub price: Option<Money>,
+    pub quantity: f64,
+    #[serde(default)]
+    pub filled_quantity: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<String>,
+    /// e.g. `2024-01-02T15:04:05+0000`
+    #[serde(default, with = "order_time", skip_serializing_if = "Option::is_none")]
+    pub entered_time: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub order_strategy_type: Option<String>,
+    #[serde(default)]
+    pub order_leg_collection: Vec<SchwabOrderLeg>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SchwabOrderLeg {
+    pub instruction: String,
+    pub quantity: f64,
+    pub instrument: SchwabInstrument,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SchwabInstrument {
+    pub symbol: String,
+    pub asset_type: String,
+}
+
+/// A date such as `divExDate`. Schwab mostly writes these as midnight UTC, e.g.
+/// `2024-11-08T00:00:00Z`, but sometimes as `2024-11-14 00:00:00.0`, so only
+/// the date part is parsed and the string is kept to be written back as sent.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct SchwabDate {
+    date: NaiveDate,
+    raw: String,
+}
+
+impl SchwabDate {
+    pub fn date(&self) -> NaiveDate {
+        self.date
+    }
+}
+
+impl TryFrom<String> for SchwabDate {
+    type Error = String;
+
+    fn try_from(raw: String) -> Result<Self, Self::Error> {
+        let date = raw
+            .get(..10)
+            .and_then(|day| NaiveDate::parse_from_str(day, "%Y-%m-%d").ok())
+            .ok_or_else(|| format!("expected a date, got {raw:?}"))?;
+
+        Ok(Self { date, raw })
+    }
+}
+
+impl From<SchwabDate> for String {
+    fn from(value: SchwabDate) -> Self {
+        value.raw
+    }
+}
+
+/// Order timestamps, e.g. `2024-01-02T15:04:05+0000`.
+mod order_time {
+    use chrono::{DateTime, Utc};
+    use serde::{Deserialize, Deserializer, Serializer, de::Error as _};
+
+    const FORMAT: &str = "%Y-%m-%dT%H:%M:%S%z";
+
+    pub fn serialize<S: Serializer>(
+        time: &Option<DateTime<Utc>>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match time {
+            Some(time) => serializer.collect_str(&time.format(FORMAT)),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<DateTime<Utc>>, D::Error> {
+        let Some(time) = Option::<String>::deserialize(deserializer)? else {
+            return Ok(None);
+        };
+
+        DateTime::parse_from_str(&time, FORMAT)
+            .map(|time| Some(time.with_timezone(&Utc)))
+            .map_err(D::Error::custom)
+    }
+}
@@ -0,0 +1,314 @@
+use aes_gcm::{
+    Aes256Gcm, Key, KeyInit, Nonce,
+    aead::{Aead, Payload},
+};
+use base64::{Engine, prelude::BASE64_STANDARD, prelude::BASE64_URL_SAFE_NO_PAD};
+use chrono::{TimeDelta, Utc};
+use db::DatabaseConnection;
+use rand::RngCore;
+use rocket::{
+    Build, Orbit, Request, Rocket,
+    fairing::{AdHoc, Fairing},
+    http::{Cookie, CookieJar, SameSite},
+    request::{FromRequest, Outcome},
+    time::Duration,
+    tokio::time::interval,
+};
+use rocket_oauth2::OAuth2;
+
+use crate::{
+    errors::ApplicationError,
+    oauth::{Credentials, CredentialsError, REFRESH_TOKEN_LIFETIME, Schwab},
+};
+
+pub(crate) static SESSION_COOKIE_NAME: &str = "__Host-session";
+
+const DEFAULT_DATABASE_URL: &str = "sqlite://mercado.db?mode=rwc";
+const NONCE_LEN: usize = 12;
+
+/// How long a session lives after it was last written. Nothing outlives its
+/// refresh token, so there's no point keeping a session any longer.
+pub const SESSION_TTL: TimeDelta = REFRESH_TOKEN_LIFETIME;
+
+/// How often stale sessions are swept from the database.
+const PRUNE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+
+/// Opaque identifier for a logged-in browser session. This is the only thing
+/// the session cookie carries; credentials live in the [`SessionStore`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SessionId(String);
+
+impl SessionId {
+    fn generate() -> Self {
+        let mut bytes = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        Self(BASE64_URL_SAFE_NO_PAD.encode(bytes))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn from_cookies(cookies: &CookieJar<'_>) -> Option<Self> {
+        cookies
+            .get_private(SESSION_COOKIE_NAME)
+            .map(|cookie| Self(cookie.value().to_owned()))
+    }
+
+    pub fn to_cookie(&self) -> Cookie<'static> {
+        Cookie::build((SESSION_COOKIE_NAME, self.0.clone()))
+            .path("/")
+            .http_only(true)
+            .secure(true)
+            .same_site(SameSite::Lax)
+            .max_age(Duration::seconds(SESSION_TTL.num_seconds()))
+            .build()
+    }
+}
+
+/// Server-side storage of OAuth credentials, encrypted at rest and keyed by [`SessionId`].
+#[derive(Clone)]
+pub struct SessionStore {
+    db: DatabaseConnection,
+    cipher: Aes256Gcm,
+}
+
+impl SessionStore {
+    pub fn new(db: DatabaseConnection, key: &[u8; 32]) -> Self {
+        Self {
+            db,
+            cipher: Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key)),
+        }
+    }
+
+    /// Persist `credentials` under a freshly minted session ID.
+    pub async fn create(&self, credentials: &Credentials) -> Result<SessionId, ApplicationError> {
+        let id = SessionId::generate();
+        let sealed = self.seal(&id, credentials);
+        db::sessions::insert(&self.db, id.as_str(), sealed)
+            .await
+            .map_err(ApplicationError::Database)?;
+        Ok(id)
+    }
+
+    pub async fn load(&self, id: &SessionId) -> Result<Option<Credentials>, ApplicationError> {
+        let stale_before = Utc::now() - SESSION_TTL;
+        let Some(sealed) = db::sessions::find(&self.db, id.as_str(), stale_before)
+            .await
+            .map_err(ApplicationError::Database)?
+        else {
+            return Ok(None);
+        };
+
+        self.open(id, &sealed)
+            .map(Some)
+            .map_err(ApplicationError::InvalidCredentials)
+    }
+
+    /// Write refreshed `credentials` back to an existing session.
+    ///
+    /// Fails with [`ApplicationError::MissingAuthentication`] if the session was
+    /// logged out in the meantime, rather than bringing it back.
+    pub async fn save(
+        &self,
+        id: &SessionId,
+        credentials: &Credentials,
+    ) -> Result<(), ApplicationError> {
+        let sealed = self.seal(id, credentials);
+        let updated = db::sessions::update(&self.db, id.as_str(), sealed)
+            .await
+            .map_err(ApplicationError::Database)?;
+
+        if !updated {
+            return Err(ApplicationError::MissingAuthentication);
+        }
+        Ok(())
+    }
+
+    pub async fn delete(&self, id: &SessionId) -> Result<(), ApplicationError> {
+        db::sessions::delete(&self.db, id.as_str())
+            .await
+            .map_err(ApplicationError::Database)?;
+        Ok(())
+    }
+
+    /// Delete sessions that haven't been written for [`SESSION_TTL`].
+    pub async fn prune(&self) -> Result<u64, ApplicationError> {
+        db::sessions::prune(&self.db, Utc::now() - SESSION_TTL)
+            .await
+            .map_err(ApplicationError::Database)
+    }
+
+    /// Encrypt `credentials`, binding the ciphertext to `id` so rows can't be swapped.
+    /// Output is `nonce || ciphertext`.
+    fn seal(&self, id: &SessionId, credentials: &Credentials) -> Vec<u8> {
+        let plaintext = serde_json::to_vec(credentials).expect("serializing should never fail");
+
+        let mut nonce = [0u8; NONCE_LEN];
+        rand::thread_rng().fill_bytes(&mut nonce);
+
+        let ciphertext = self
+            .cipher
+            .encrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: &plaintext,
+                    aad: id.as_str().as_bytes(),
+                },
+            )
+            .expect("encrypting should never fail");
+
+        [nonce.as_slice(), &ciphertext].concat()
+    }
+
+    fn open(&self, id: &SessionId, sealed: &[u8]) -> Result<Credentials, CredentialsError> {
+        if sealed.len() < NONCE_LEN {
+            return Err(CredentialsError::Cipher);
+        }
+
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+
+        let plaintext = self
+            .cipher
+            .decrypt(
+                Nonce::from_slice(nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad: id.as_str().as_bytes(),
+                },
+            )
+            .map_err(|_| CredentialsError::Cipher)?;
+
+        Ok(serde_json::from_slice(&plaintext)?)
+    }
+}
+
+/// Request guard for endpoints that need a logged-in user.
+///
+/// Loads the session's credentials, refreshing (and persisting) the access token
+/// if it is about to expire.
+pub struct AuthenticatedUser {
+    pub session: SessionId,
+    pub credentials: Credentials,
+}
+
+impl AuthenticatedUser {
+    async fn authenticate(request: &Request<'_>) -> Result<Self, ApplicationError> {
+        let session = SessionId::from_cookies(request.cookies())
+            .ok_or(ApplicationError::MissingAuthentication)?;
+
+        let sessions = request
+            .rocket()
+            .state::<SessionStore>()
+            .expect("session store fairing should be attached");
+
+        let mut credentials = sessions
+            .load(&session)
+            .await?
+            .ok_or(ApplicationError::MissingAuthentication)?;
+
+        if credentials.is_expired() {
+            let oauth2 = request
+                .guard::<OAuth2<Schwab>>()
+                .await
+                .succeeded()
+                .expect("oauth fairing should be attached");
+
+            credentials
+                .refresh_access_token(&oauth2)
+                .await
+                .map_err(ApplicationError::InvalidCredentials)?;
+
+            sessions.save(&session, &credentials).await?;
+        }
+
+        Ok(Self {
+            session,
+            credentials,
+        })
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for AuthenticatedUser {
+    type Error = ApplicationError;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        match Self::authenticate(request).await {
+            Ok(user) => Outcome::Success(user),
+            Err(e) => e.defer(request),
+        }
+    }
+}
+
+async fn init(rocket: Rocket<Build>) -> Result<Rocket<Build>, Rocket<Build>> {
+    let figment = rocket.figment();
+
+    let url = figment
+        .extract_inner::<String>("database_url")
+        .unwrap_or_else(|_| DEFAULT_DATABASE_URL.to_owned());
+
+    let key = match figment.extract_inner::<String>("session_key") {
+        Ok(encoded) => match BASE64_STANDARD.decode(encoded).map(<[u8; 32]>::try_from) {
+            Ok(Ok(key)) => key,
+            _ => {
+                error!("`session_key` must be 32 bytes, base64 encoded");
+                return Err(rocket);
+            }
+        },
+        Err(_) if figment.profile() == rocket::Config::RELEASE_PROFILE => {
+            error!("`session_key` is required in release builds");
+            return Err(rocket);
+        }
+        Err(_) => {
+            warn!("`session_key` is not set; sessions will not survive a restart");
+            let mut key = [0u8; 32];
+            rand::thread_rng().fill_bytes(&mut key);
+            key
+        }
+    };
+
+    let db = match db::connect(&url).await {
+        Ok(db) => db,
+        Err(e) => {
+            error!("could not open session database {url}: {e}");
+            return Err(rocket);
+        }
+    };
+
+    Ok(rocket
+        .manage(SessionStore::new(db, &key))
+        .attach(AdHoc::on_liftoff("Session pruning", |rocket| {
+            Box::pin(async move { prune_periodically(rocket) })
+        })))
+}
+
+/// Sweep stale sessions now and then every [`PRUNE_INTERVAL`] until shutdown.
+fn prune_periodically(rocket: &Rocket<Orbit>) {
+    let sessions = rocket
+        .state::<SessionStore>()
+        .expect("session store should be managed on ignite")
+        .clone();
+    let mut shutdown = rocket.shutdown();
+
+    rocket::tokio::spawn(async move {
+        let mut ticks = interval(PRUNE_INTERVAL);
+
+        loop {
+            rocket::tokio::select! {
+                _ = ticks.tick() => {}
+                _ = &mut shutdown => break,
+            }
+
+            match sessions.prune().await {
+                Ok(0) => {}
+                Ok(pruned) => info!("pruned {pruned} stale sessions"),
+                Err(e) => warn!("could not prune stale sessions: {e}"),
+            }
+        }
+    });
+}
+
+pub fn fairing() -> impl Fairing {
+    AdHoc::try_on_ignite("Session store", init)
+}
@@ -0,0 +1,546 @@
+use std::{fmt, str::FromStr};
+
+use chrono::NaiveDate;
+use rocket::form::{self, FromFormField, ValueField};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(symbol: &str) -> Symbol {
+        symbol.parse().unwrap()
+    }
+
+    fn reason(symbol: &str) -> SymbolError {
+        symbol.parse::<Symbol>().unwrap_err().reason
+    }
+
+    #[test]
+    fn equities_and_indices() {
+        assert_eq!(parse("AAPL").kind(), &SymbolKind::Equity);
+        assert_eq!(parse(" brk/b ").as_str(), "BRK/B");
+        assert_eq!(parse("BF.B").kind(), &SymbolKind::Equity);
+        assert_eq!(parse("$SPX").kind(), &SymbolKind::Index);
+
+        assert_eq!(reason(""), SymbolError::Empty);
+        assert_eq!(reason("AA@PL"), SymbolError::InvalidCharacter('@'));
+        assert_eq!(reason("BRK/"), SymbolError::InvalidShareClass);
+        assert_eq!(reason("TOOLONGX"), SymbolError::InvalidRoot);
+        // a space only makes an option symbol when it pads an OCC root
+        assert_eq!(reason("AA PL"), SymbolError::InvalidCharacter(' '));
+        assert_eq!(reason("BRK B"), SymbolError::InvalidCharacter(' '));
+        assert_eq!(reason("AAPL 251219"), SymbolError::InvalidCharacter(' '));
+        assert_eq!(reason("$"), SymbolError::InvalidRoot);
+    }
+
+    #[test]
+    fn forex_pairs() {
+        assert_eq!(
+            parse("eur/usd").kind(),
+            &SymbolKind::Forex {
+                base: "EUR".to_owned(),
+                quote: "USD".to_owned()
+            }
+        );
+    }
+
+    #[test]
+    fn occ_options() {
+        let option = parse("AAPL  251219C00200000");
+        assert_eq!(
+            option.kind(),
+            &SymbolKind::Option(OptionContract {
+                root: "AAPL".to_owned(),
+                expiry: NaiveDate::from_ymd_opt(2025, 12, 19).unwrap(),
+                right: OptionRight::Call,
+                strike: 200_000,
+            })
+        );
+
+        // unpadded roots are padded to the 21 characters Schwab expects
+        assert_eq!(
+            parse("spy251219p00612500").as_str(),
+            "SPY   251219P00612500"
+        );
+
+        assert_eq!(
+            reason("AAPL 251219C00200000"),
+            SymbolError::InvalidOptionLength
+        );
+        assert_eq!(reason("AAPL  251319C00200000"), SymbolError::InvalidExpiry);
+        assert_eq!(reason("AAPL  2512X9C0020000Z"), SymbolError::InvalidExpiry);
+        assert_eq!(
+            reason("AAPL  251219X00200000"),
+            SymbolError::InvalidRight('X')
+        );
+        assert_eq!(
+            reason("AAPL  2512190C0200000"),
+            SymbolError::InvalidRight('0')
+        );
+    }
+
+    #[test]
+    fn futures() {
+        assert_eq!(
+            parse("/ES").kind(),
+            &SymbolKind::Future {
+                root: "ES".to_owned(),
+                contract: None
+            }
+        );
+        assert_eq!(
+            parse("/6EZ25").kind(),
+            &SymbolKind::Future {
+                root: "6E".to_owned(),
+                contract: Some(ContractMonth {
+                    year: 2025,
+                    month: 12
+                })
+            }
+        );
+
+        assert_eq!(reason("/ESA25"), SymbolError::InvalidMonthCode('A'));
+        assert_eq!(reason("/"), SymbolError::InvalidRoot);
+    }
+
+    #[test]
+    fn future_options() {
+        assert_eq!(
+            parse("./OZCZ23C565").kind(),
+            &SymbolKind::FutureOption {
+                root: "OZC".to_owned(),
+                contract: ContractMonth {
+                    year: 2023,
+                    month: 12
+                },
+                right: OptionRight::Call,
+                strike: 565_000,
+            }
+        );
+        assert!(matches!(
+            parse("./ZNH26P111.5").kind(),
+            SymbolKind::FutureOption {
+                strike: 111_500,
+                right: OptionRight::Put,
+                ..
+            }
+        ));
+
+        assert_eq!(reason("./OZCZ23C"), SymbolError::InvalidStrike);
+        assert_eq!(reason("./OZCZ23X565"), SymbolError::InvalidRight('X'));
+        assert_eq!(reason("./OZCC565"), SymbolError::MissingContract);
+    }
+
+    #[test]
+    fn serde_round_trip() {
+        let symbols: Vec<Symbol> = serde_json::from_str(r#"["aapl", "/ESZ25"]"#).unwrap();
+        assert_eq!(
+            serde_json::to_string(&symbols).unwrap(),
+            r#"["AAPL","/ESZ25"]"#
+        );
+
+        assert!(serde_json::from_str::<Symbol>(r#""AA PL""#).is_err());
+    }
+}
+
+/// Longest input considered at all, comfortably above any real symbol.
+const MAX_LEN: usize = 32;
+
+/// A validated ticker symbol, normalized to the format Schwab expects.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+#[serde(try_from = "String", into = "String")]
+pub struct Symbol {
+    symbol: String,
+    kind: SymbolKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum SymbolKind {
+    /// a stock or fund, e.g. `AAPL`, with an optional share class as in `BRK/B`
+    Equity,
+    /// an index, e.g. `$SPX`
+    Index,
+    /// a currency pair, e.g. `EUR/USD`
+    Forex { base: String, quote: String },
+    /// a future, e.g. `/ES` for the front month or `/ESZ25` for a given contract
+    Future {
+        root: String,
+        contract: Option<ContractMonth>,
+    },
+    /// an equity or index option in OCC format, e.g. `AAPL  251219C00200000`
+    Option(OptionContract),
+    /// an option on a future, e.g. `./OZCZ23C565`
+    FutureOption {
+        root: String,
+        contract: ContractMonth,
+        right: OptionRight,
+        /// in thousandths, as for [`OptionContract::strike`]
+        strike: u64,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OptionRight {
+    Call,
+    Put,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct OptionContract {
+    pub root: String,
+    pub expiry: NaiveDate,
+    pub right: OptionRight,
+    /// in thousandths of the quote currency, as OCC symbols write it
+    pub strike: u64,
+}
+
+/// The delivery month of a futures contract.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ContractMonth {
+    pub year: i32,
+    /// 1 to 12
+    pub month: u32,
+}
+
+/// Why a symbol was rejected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error, Serialize)]
+pub enum SymbolError {
+    #[error("symbol is empty")]
+    Empty,
+    #[error("symbol is longer than {MAX_LEN} characters")]
+    TooLong,
+    #[error("unexpected character {0:?}")]
+    InvalidCharacter(char),
+    #[error("expected a root of one to six letters or digits")]
+    InvalidRoot,
+    #[error("expected a share class of one or two letters after the separator")]
+    InvalidShareClass,
+    #[error("OCC option symbols are 21 characters, with the root padded to six")]
+    InvalidOptionLength,
+    #[error("expected an expiry date as YYMMDD")]
+    InvalidExpiry,
+    #[error("expected C or P for call or put, not {0:?}")]
+    InvalidRight(char),
+    #[error("expected a strike price")]
+    InvalidStrike,
+    #[error("expected a month code and two-digit year")]
+    MissingContract,
+    #[error("{0:?} is not a futures month code")]
+    InvalidMonthCode(char),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error, Serialize)]
+#[error("invalid symbol {symbol:?}: {reason}")]
+pub struct InvalidSymbol {
+    pub symbol: String,
+    pub reason: SymbolError,
+}
+
+impl Symbol {
+    pub fn as_str(&self) -> &str {
+        &self.symbol
+    }
+
+    pub fn kind(&self) -> &SymbolKind {
+        &self.kind
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.symbol)
+    }
+}
+
+impl From<Symbol> for String {
+    fn from(value: Symbol) -> Self {
+        value.symbol
+    }
+}
+
+impl TryFrom<String> for Symbol {
+    type Error = InvalidSymbol;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl FromStr for Symbol {
+    type Err = InvalidSymbol;
+
+    /// Parse `s`, ignoring case and surrounding whitespace.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let symbol = s.trim().to_ascii_uppercase();
+
+        parse(&symbol)
+            .map(|(symbol, kind)| Self { symbol, kind })
+            .map_err(|reason| InvalidSymbol {
+                symbol: s.to_owned(),
+                reason,
+            })
+    }
+}
+
+impl<'v> FromFormField<'v> for Symbol {
+    fn from_value(field: ValueField<'v>) -> form::Result<'v, Self> {
+        field
+            .value
+            .parse()
+            .map_err(|e| form::Error::custom(e).into())
+    }
+}
+
+/// Comma separated symbols, as in `?symbols=AAPL,MSFT`. Empty entries are skipped.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SymbolList(pub Vec<Symbol>);
+
+impl<'v> FromFormField<'v> for SymbolList {
+    fn from_value(field: ValueField<'v>) -> form::Result<'v, Self> {
+        field
+            .value
+            .split(',')
+            .filter(|symbol| !symbol.trim().is_empty())
+            .map(str::parse)
+            .collect::<Result<_, _>>()
+            .map(SymbolList)
+            .map_err(|e: InvalidSymbol| form::Error::custom(e).into())
+    }
+}
+
+impl InvalidSymbol {
+    /// The first invalid symbol among a form's errors, if that is what failed it.
+    pub fn from_form(errors: &form::Errors<'_>) -> Option<Self> {
+        errors.iter().find_map(|error| match &error.kind {
+            form::error::ErrorKind::Custom(_, e) => e.downcast_ref::<Self>().cloned(),
+            _ => None,
+        })
+    }
+}
+
+/// Parse an uppercased, trimmed symbol into its canonical form and kind.
+fn parse(symbol: &str) -> Result<(String, SymbolKind), SymbolError> {
+    if symbol.is_empty() {
+        return Err(SymbolError::Empty);
+    }
+    if symbol.len() > MAX_LEN {
+        return Err(SymbolError::TooLong);
+    }
+    if let Some(c) = symbol
+        .chars()
+        .find(|c| !c.is_ascii_alphanumeric() && !"/.$ ".contains(*c))
+    {
+        return Err(SymbolError::InvalidCharacter(c));
+    }
+
+    if let Some(rest) = symbol.strip_prefix("./") {
+        let kind = future_option(rest)?;
+        return Ok((symbol.to_owned(), kind));
+    }
+
+    if let Some(rest) = symbol.strip_prefix('/') {
+        let kind = future(rest)?;
+        return Ok((symbol.to_owned(), kind));
+    }
+
+    if let Some(rest) = symbol.strip_prefix('$') {
+        root(rest, 6)?;
+        return Ok((symbol.to_owned(), SymbolKind::Index));
+    }
+
+    // OCC symbols end in eight strike digits; nothing else is that long and numeric
+    let numeric_tail = symbol.len() > 15
+        && symbol[symbol.len() - 8..]
+            .bytes()
+            .all(|b| b.is_ascii_digit());
+    // or are 21 characters with the root padded to six; other spaces are just invalid
+    let padded_root = symbol.len() == 21 && {
+        let base = symbol[..6].trim_end();
+        !base.is_empty() && base.len() < 6 && !base.contains(' ')
+    };
+    if numeric_tail || padded_root {
+        return option(symbol).map(|(symbol, contract)| (symbol, SymbolKind::Option(contract)));
+    }
+
+    if let Some((base, quote)) = symbol.split_once('/')
+        && base.len() == 3
+        && quote.len() == 3
+        && symbol.bytes().all(|b| b == b'/' || b.is_ascii_alphabetic())
+    {
+        let kind = SymbolKind::Forex {
+            base: base.to_owned(),
+            quote: quote.to_owned(),
+        };
+        return Ok((symbol.to_owned(), kind));
+    }
+
+    equity(symbol)?;
+    Ok((symbol.to_owned(), SymbolKind::Equity))
+}
+
+/// Check a root of one to `max` letters or digits.
+fn root(root: &str, max: usize) -> Result<(), SymbolError> {
+    // a stray character is the more useful thing to report, even if the root is also too long
+    if let Some(c) = root.chars().find(|c| !c.is_ascii_alphanumeric()) {
+        return Err(SymbolError::InvalidCharacter(c));
+    }
+
+    if root.is_empty() || root.len() > max {
+        return Err(SymbolError::InvalidRoot);
+    }
+
+    Ok(())
+}
+
+fn equity(symbol: &str) -> Result<(), SymbolError> {
+    let Some((base, class)) = symbol.split_once(['/', '.']) else {
+        return root(symbol, 6);
+    };
+
+    root(base, 6)?;
+
+    if class.is_empty() || class.len() > 2 || !class.bytes().all(|b| b.is_ascii_alphabetic()) {
+        return Err(SymbolError::InvalidShareClass);
+    }
+
+    Ok(())
+}
+
+fn right(c: char) -> Result<OptionRight, SymbolError> {
+    match c {
+        'C' => Ok(OptionRight::Call),
+        'P' => Ok(OptionRight::Put),
+        c => Err(SymbolError::InvalidRight(c)),
+    }
+}
+
+/// A contract month written as a month code and two-digit year, e.g. `Z25`.
+fn contract_month(code: &str) -> Result<ContractMonth, SymbolError> {
+    let mut chars = code.chars();
+    let (Some(letter), year) = (chars.next(), chars.as_str()) else {
+        return Err(SymbolError::MissingContract);
+    };
+    if year.len() != 2 || !year.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(SymbolError::MissingContract);
+    }
+
+    let month = "FGHJKMNQUVXZ"
+        .find(letter)
+        .ok_or(SymbolError::InvalidMonthCode(letter))?;
+
+    Ok(ContractMonth {
+        year: 2000 + year.parse::<i32>().expect("two digits"),
+        month: month as u32 + 1,
+    })
+}
+
+/// `/ES` or `/ESZ25`, without the slash.
+fn future(rest: &str) -> Result<SymbolKind, SymbolError> {
+    let dated = rest.len() >= 4
+        && rest[rest.len() - 2..].bytes().all(|b| b.is_ascii_digit())
+        && rest.as_bytes()[rest.len() - 3].is_ascii_alphabetic();
+
+    let (base, contract) = if dated {
+        let (base, code) = rest.split_at(rest.len() - 3);
+        (base, Some(contract_month(code)?))
+    } else {
+        (rest, None)
+    };
+
+    root(base, 4)?;
+
+    Ok(SymbolKind::Future {
+        root: base.to_owned(),
+        contract,
+    })
+}
+
+/// `OZCZ23C565`, i.e. a future option without the leading `./`.
+fn future_option(rest: &str) -> Result<SymbolKind, SymbolError> {
+    let split = rest
+        .rfind(|c: char| !c.is_ascii_digit() && c != '.')
+        .ok_or(SymbolError::MissingContract)?;
+    let (head, strike) = rest.split_at(split + 1);
+
+    let strike = parse_strike(strike)?;
+
+    let (head, letter) = head.split_at(head.len() - 1);
+    let right = right(letter.chars().next().expect("split after a character"))?;
+
+    if head.len() < 3 {
+        return Err(SymbolError::MissingContract);
+    }
+    let (base, code) = head.split_at(head.len() - 3);
+    let contract = contract_month(code)?;
+    root(base, 4)?;
+
+    Ok(SymbolKind::FutureOption {
+        root: base.to_owned(),
+        contract,
+        right,
+        strike,
+    })
+}
+
+/// A strike like `565` or `111.5`, in thousandths.
+fn parse_strike(strike: &str) -> Result<u64, SymbolError> {
+    let (whole, fraction) = strike.split_once('.').unwrap_or((strike, ""));
+
+    if whole.is_empty()
+        || fraction.len() > 3
+        || !whole
+            .bytes()
+            .chain(fraction.bytes())
+            .all(|b| b.is_ascii_digit())
+    {
+        return Err(SymbolError::InvalidStrike);
+    }
+
+    let whole: u64 = whole.parse().map_err(|_| SymbolError::InvalidStrike)?;
+    let fraction: u64 = format!("{fraction:0<3}").parse().expect("three digits");
+
+    whole
+        .checked_mul(1000)
+        .and_then(|whole| whole.checked_add(fraction))
+        .ok_or(SymbolError::InvalidStrike)
+}
+
+/// An OCC symbol, padding the root if it came without its spaces.
+fn option(symbol: &str) -> Result<(String, OptionContract), SymbolError> {
+    if symbol.len() <= 15 {
+        return Err(SymbolError::InvalidOptionLength);
+    }
+    let (padded, tail) = symbol.split_at(symbol.len() - 15);
+
+    let base = padded.trim_end();
+    if padded.len() != base.len() && padded.len() != 6 {
+        return Err(SymbolError::InvalidOptionLength);
+    }
+    root(base, 6)?;
+
+    let (expiry, rest) = tail.split_at(6);
+    let (letter, strike) = rest.split_at(1);
+
+    let expiry = expiry
+        .bytes()
+        .all(|b| b.is_ascii_digit())
+        .then(|| NaiveDate::parse_from_str(expiry, "%y%m%d").ok())
+        .flatten()
+        .ok_or(SymbolError::InvalidExpiry)?;
+    let right = right(letter.chars().next().expect("one character"))?;
+    if !strike.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(SymbolError::InvalidStrike);
+    }
+
+    let contract = OptionContract {
+        root: base.to_owned(),
+        expiry,
+        right,
+        strike: strike.parse().expect("eight digits"),
+    };
+
+    Ok((format!("{base:<6}{tail}"), contract))
+}
@@ -0,0 +1,241 @@
+//! Boots the backend against an in-process mock Schwab server.
+//!
+//! The mock listens on a real port since the backend reaches it over HTTP, both
+//! for the OAuth token exchange and for API calls. The backend itself is driven
+//! through Rocket's local client, or served on a real port for WebSocket tests.
+
+#![allow(dead_code)]
+
+use std::{net::TcpListener, path::PathBuf, sync::Mutex, time::Duration};
+
+use base64::{Engine, prelude::BASE64_STANDARD};
+use mock_schwab::{FaultRule, MockConfig};
+use rand::RngCore;
+use rocket::{
+    Build, Config, Rocket, Shutdown,
+    figment::{Figment, providers::Serialized},
+    http::Status,
+    local::asynchronous::Client,
+    tokio::{self, net::TcpStream, time::sleep},
+};
+use serde_json::Value;
+
+/// Where the mock sends the browser after "logging in". Never dialed; the
+/// harness hands the path and query to the local client instead.
+const REDIRECT_ORIGIN: &str = "http://127.0.0.1";
+
+fn free_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0")
+        .and_then(|listener| listener.local_addr())
+        .expect("should find a free port")
+        .port()
+}
+
+fn random_key() -> String {
+    let mut key = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut key);
+    BASE64_STANDARD.encode(key)
+}
+
+/// Launch `rocket` in the background, returning once it accepts connections.
+async fn launch(rocket: Rocket<Build>) -> Shutdown {
+    let rocket = rocket.ignite().await.expect("server should ignite");
+    let port = rocket.config().port;
+    let shutdown = rocket.shutdown();
+
+    tokio::spawn(rocket.launch());
+
+    for _ in 0..200 {
+        if TcpStream::connect(("127.0.0.1", port)).await.is_ok() {
+            return shutdown;
+        }
+        sleep(Duration::from_millis(10)).await;
+    }
+
+    panic!("server on port {port} never started listening");
+}
+
+pub struct Harness {
+    /// the backend, with cookies carried between requests
+    pub client: Client,
+    pub mock_url: String,
+    figment: Figment,
+    database: PathBuf,
+    servers: Mutex<Vec<Shutdown>>,
+}
+
+impl Harness {
+    pub async fn start() -> Self {
+        Self::with_mock(MockConfig::default()).await
+    }
+
+    pub async fn with_mock(config: MockConfig) -> Self {
+        Self::configured(config, |figment| figment).await
+    }
+
+    /// Start with `configure` applied on top of the backend's test configuration.
+    pub async fn configured(
+        config: MockConfig,
+        configure: impl FnOnce(Figment) -> Figment,
+    ) -> Self {
+        let mock_port = free_port();
+        let mock = launch(mock_schwab::build(
+            mock_schwab::figment()
+                .merge(Serialized::globals(&config))
+                .merge(("port", mock_port))
+                .merge(("log_level", "off")),
+        ))
+        .await;
+
+        let mock_url = format!("http://127.0.0.1:{mock_port}");
+        let database = std::env::temp_dir().join(format!("mercado-test-{}.db", free_port()));
+
+        let figment = Config::figment()
+            .merge(("log_level", "off"))
+            .merge(("secret_key", random_key()))
+            .merge(("session_key", random_key()))
+            .merge((
+                "database_url",
+                format!("sqlite://{}?mode=rwc", database.display()),
+            ))
+            .merge(("oauth.schwab.client_id", "mock-client"))
+            .merge(("oauth.schwab.client_secret", "mock-secret"))
+            .merge((
+                "oauth.schwab.redirect_uri",
+                format!("{REDIRECT_ORIGIN}/u/auth/schwab"),
+            ))
+            .merge((
+                "oauth.schwab.auth_uri",
+                format!("{mock_url}/v1/oauth/authorize"),
+            ))
+            .merge((
+                "oauth.schwab.token_uri",
+                format!("{mock_url}/v1/oauth/token"),
+            ))
+            .merge((
+                "oauth.schwab.revoke_uri",
+                format!("{mock_url}/v1/oauth/revoke"),
+            ))
+            .merge(("schwab.trader_api", format!("{mock_url}/trader/v1")))
+            .merge((
+                "schwab.market_data_api",
+                format!("{mock_url}/marketdata/v1"),
+            ));
+
+        let figment = configure(figment);
+
+        let client = Client::tracked(backend::rocket().configure(figment.clone()))
+            .await
+            .expect("backend should ignite");
+
+        Self {
+            client,
+            mock_url,
+            figment,
+            database,
+            servers: Mutex::new(vec![mock]),
+        }
+    }
+
+    /// Go through the whole OAuth dance, leaving the client with a session cookie.
+    pub async fn login(&self) {
+        self.login_from("/u/login/schwab").await;
+    }
+
+    /// Log in through `path`, returning where the callback sends the browser afterwards.
+    pub async fn login_from(&self, path: &str) -> String {
+        let response = self.client.get(path.to_owned()).dispatch().await;
+        assert_eq!(response.status(), Status::SeeOther);
+        let authorize = response
+            .headers()
+            .get_one("Location")
+            .expect("login should redirect to the authorize endpoint")
+            .to_owned();
+
+        let approved = reqwest::Client::builder()
+            .redirect(reqwest::redirect::Policy::none())
+            .build()
+            .unwrap()
+            .get(&authorize)
+            .send()
+            .await
+            .expect("mock should approve the login");
+        let callback = approved.headers()["location"]
+            .to_str()
+            .unwrap()
+            .strip_prefix(REDIRECT_ORIGIN)
+            .expect("mock should redirect to the configured redirect_uri")
+            .to_owned();
+
+        let response = self.client.get(callback).dispatch().await;
+        assert_eq!(response.status(), Status::SeeOther);
+        let session = response
+            .cookies()
+            .get("__Host-session")
+            .expect("callback should set the session cookie");
+        // the cookie goes when the server would forget the session anyway
+        assert_eq!(session.max_age(), Some(rocket::time::Duration::days(7)));
+        assert!(self.session_cookie().is_some());
+
+        response
+            .headers()
+            .get_one("Location")
+            .expect("callback should redirect")
+            .to_owned()
+    }
+
+    /// The session cookie as the browser would send it back, i.e. still encrypted.
+    pub fn session_cookie(&self) -> Option<String> {
+        self.client
+            .cookies()
+            .get("__Host-session")
+            .map(|cookie| cookie.value().to_owned())
+    }
+
+    /// `GET` a backend path, returning the status and JSON body.
+    pub async fn get_json(&self, path: &str) -> (Status, Value) {
+        let response = self.client.get(path.to_owned()).dispatch().await;
+        let status = response.status();
+        let body = response.into_json().await.expect("body should be JSON");
+        (status, body)
+    }
+
+    pub async fn inject(&self, rule: FaultRule) {
+        reqwest::Client::new()
+            .post(format!("{}/_mock/faults", self.mock_url))
+            .json(&rule)
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .expect("mock should accept the fault");
+    }
+
+    pub async fn clear_faults(&self) {
+        reqwest::Client::new()
+            .delete(format!("{}/_mock/faults", self.mock_url))
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .expect("mock should clear its faults");
+    }
+
+    /// Serve the backend on a real port, sharing this harness' keys and database
+    /// so the local client's session cookie works there too.
+    pub async fn serve(&self) -> u16 {
+        let port = free_port();
+        let shutdown =
+            launch(backend::rocket().configure(self.figment.clone().merge(("port", port)))).await;
+
+        self.servers.lock().unwrap().push(shutdown);
+        port
+    }
+}
+
+impl Drop for Harness {
+    fn drop(&mut self) {
+        for server in self.servers.lock().unwrap().drain(..) {
+            server.notify();
+        }
+        let _ = std::fs::remove_file(&self.database);
+    }
+}
@@ -0,0 +1,509 @@
+mod common;
+
+use common::Harness;
+use mock_schwab::{Fault, FaultRule, MockConfig, Scope};
+use rocket::http::{ContentType, Status};
+use serde_json::json;
+
+#[rocket::async_test]
+async fn login_then_user() {
+    let harness = Harness::start().await;
+    harness.login().await;
+
+    let (status, body) = harness.get_json("/u/user").await;
+    assert_eq!(status, Status::Ok);
+    assert_eq!(body[0]["account_number"], "12345678");
+    assert_eq!(body[0]["primary"], true);
+}
+
+#[rocket::async_test]
+async fn price_history() {
+    let harness = Harness::start().await;
+    harness.login().await;
+
+    let (status, body) = harness.get_json("/u/history/AAPL?period=week").await;
+    assert_eq!(status, Status::Ok);
+    assert_eq!(body["symbol"], "AAPL");
+
+    // five days of thirty minute candles
+    let candles = body["candles"].as_array().unwrap();
+    assert_eq!(candles.len(), 5 * 48);
+    for candle in candles {
+        assert!(
+            candle["low"].as_f64() <= candle["high"].as_f64(),
+            "{candle}"
+        );
+    }
+}
+
+#[rocket::async_test]
+async fn orders() {
+    let harness = Harness::start().await;
+    harness.login().await;
+
+    let (status, body) = harness.get_json("/u/accounts/12345678/orders").await;
+    assert_eq!(status, Status::Ok);
+
+    // the mock's option spread has two legs, which orders can't represent
+    let orders = body.as_array().unwrap();
+    assert_eq!(orders.len(), 2, "{body}");
+    assert_eq!(orders[0]["id"], "1001");
+    assert_eq!(orders[0]["status"], "filled");
+    assert_eq!(orders[1]["side"], "sell");
+    assert_eq!(orders[1]["limit_price"], 512.25);
+
+    let (status, body) = harness.get_json("/u/accounts/87654321/orders").await;
+    assert_eq!(status, Status::NotFound);
+    assert_eq!(body, json!({ "error": { "UnknownAccount": "87654321" } }));
+}
+
+#[rocket::async_test]
+async fn only_the_paper_broker_places_orders() {
+    let order = json!({ "symbol": "AAPL", "side": "buy", "quantity": 1, "order_type": "market" });
+
+    let harness = Harness::start().await;
+    harness.login().await;
+
+    let response = harness
+        .client
+        .post("/u/accounts/12345678/orders")
+        .header(ContentType::JSON)
+        .body(order.to_string())
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::NotImplemented);
+
+    let harness = Harness::configured(MockConfig::default(), |figment| {
+        figment.merge(("broker", "paper"))
+    })
+    .await;
+    harness.login().await;
+
+    let place = |content_type| {
+        harness
+            .client
+            .post("/u/accounts/PAPER-0001/orders")
+            .header(content_type)
+            .body(order.to_string())
+            .dispatch()
+    };
+
+    // a cross-site form can't send JSON, so it can't place orders either
+    let response = place(ContentType::Form).await;
+    assert_ne!(response.status(), Status::Ok);
+
+    let response = place(ContentType::JSON).await;
+    assert_eq!(response.status(), Status::Ok);
+    let placed: serde_json::Value = response.into_json().await.unwrap();
+    assert_eq!(placed["status"], "filled");
+
+    let (status, body) = harness.get_json("/u/accounts/PAPER-0001/orders").await;
+    assert_eq!(status, Status::Ok);
+    assert_eq!(body[0]["id"], placed["id"]);
+}
+
+#[rocket::async_test]
+async fn requires_login() {
+    let harness = Harness::start().await;
+
+    for path in ["/u/user", "/u/session", "/u/quotes?symbols=AAPL"] {
+        let (status, body) = harness.get_json(path).await;
+        assert_eq!(status, Status::Unauthorized, "{path}");
+        assert_eq!(body, json!({ "error": "MissingAuthentication" }), "{path}");
+    }
+}
+
+#[rocket::async_test]
+async fn login_rejects_foreign_redirects() {
+    let harness = Harness::start().await;
+
+    let (status, body) = harness
+        .get_json("/u/login/schwab?next=//evil.example")
+        .await;
+    assert_eq!(status, Status::BadRequest);
+    assert_eq!(
+        body,
+        json!({ "error": { "InvalidRedirect": "//evil.example" } })
+    );
+}
+
+#[rocket::async_test]
+async fn login_redirects_only_to_its_own_next() {
+    let harness = Harness::start().await;
+
+    assert_eq!(harness.login_from("/u/login/schwab?next=/a").await, "/a");
+
+    // an abandoned login's destination doesn't carry over to the next login
+    let response = harness
+        .client
+        .get("/u/login/schwab?next=/b")
+        .dispatch()
+        .await;
+    assert_eq!(response.status(), Status::SeeOther);
+    assert_eq!(harness.login_from("/u/login/schwab").await, "/");
+}
+
+#[rocket::async_test]
+async fn quotes() {
+    let harness = Harness::start().await;
+    harness.login().await;
+
+    let (status, body) = harness.get_json("/u/quotes?symbols=AAPL,MSFT").await;
+    assert_eq!(status, Status::Ok);
+    assert_eq!(body["AAPL"]["symbol"], "AAPL");
+    assert_eq!(body["MSFT"]["symbol"], "MSFT");
+    assert!(body["AAPL"]["quote"]["lastPrice"].as_f64().unwrap() > 0.0);
+
+    let (status, body) = harness.get_json("/u/quotes").await;
+    assert_eq!(status, Status::BadRequest);
+    assert_eq!(
+        body,
+        json!({ "error": { "MissingQueryParameters": ["symbols"] } })
+    );
+}
+
+#[rocket::async_test]
+async fn quotes_for_special_symbols() {
+    let harness = Harness::start().await;
+    harness.login().await;
+
+    // a share class, an index and a future, each needing percent-encoding
+    let (status, body) = harness
+        .get_json("/u/quotes?symbols=BRK%2FB,%24SPX,%2FESZ25")
+        .await;
+    assert_eq!(status, Status::Ok);
+
+    for symbol in ["BRK/B", "$SPX", "/ESZ25"] {
+        assert_eq!(body[symbol]["symbol"], symbol, "{body}");
+    }
+}
+
+#[rocket::async_test]
+async fn normalized_quotes() {
+    let harness = Harness::start().await;
+    harness.login().await;
+
+    let (status, body) = harness
+        .get_json("/u/quotes?symbols=AAPL&format=normalized")
+        .await;
+    assert_eq!(status, Status::Ok);
+
+    let aapl = &body["quotes"]["AAPL"];
+    assert_eq!(aapl["asset_class"], "equity");
+    assert!(aapl["bid"].as_f64().unwrap() <= aapl["ask"].as_f64().unwrap());
+    assert!(aapl["last"].as_f64().unwrap() > 0.0);
+    assert_eq!(body["invalid_symbols"], json!([]));
+}
+
+#[rocket::async_test]
+async fn paper_quotes_are_normalized() {
+    let harness = Harness::configured(MockConfig::default(), |figment| {
+        figment.merge(("broker", "paper"))
+    })
+    .await;
+    harness.login().await;
+
+    let (status, body) = harness.get_json("/u/quotes?symbols=AAPL").await;
+    assert_eq!(status, Status::Ok);
+    assert_eq!(body["quotes"]["AAPL"]["asset_class"], "equity", "{body}");
+
+    // there is no Schwab response to pass through
+    let (status, body) = harness
+        .get_json("/u/quotes?symbols=AAPL&format=schwab")
+        .await;
+    assert_eq!(status, Status::BadRequest);
+    assert_eq!(
+        body,
+        json!({ "error": { "UnsupportedQuoteFormat": "schwab" } })
+    );
+}
+
+#[rocket::async_test]
+async fn quotes_are_served_from_cache() {
+    let harness = Harness::configured(MockConfig::default(), |figment| {
+        figment.merge(("quotes.max_age_ms", 60_000))
+    })
+    .await;
+    harness.login().await;
+
+    let (status, first) = harness.get_json("/u/quotes?symbols=AAPL").await;
+    assert_eq!(status, Status::Ok);
+
+    harness
+        .inject(FaultRule {
+            fault: Fault::Malformed,
+            scope: Scope::MarketData,
+            count: None,
+        })
+        .await;
+
+    // nothing to fetch, so the broken upstream goes unnoticed
+    let (status, cached) = harness.get_json("/u/quotes?symbols=AAPL").await;
+    assert_eq!(status, Status::Ok);
+    assert_eq!(cached, first);
+
+    // MSFT isn't cached and has to be fetched
+    let (status, _) = harness.get_json("/u/quotes?symbols=AAPL,MSFT").await;
+    assert_ne!(status, Status::Ok);
+
+    harness.clear_faults().await;
+
+    let (status, body) = harness.get_json("/u/quotes?symbols=AAPL,MSFT").await;
+    assert_eq!(status, Status::Ok);
+    assert_eq!(body["AAPL"], first["AAPL"]);
+    assert_eq!(body["MSFT"]["symbol"], "MSFT");
+}
+
+#[rocket::async_test]
+async fn schema_drift_is_opt_in() {
+    let harness = Harness::start().await;
+    harness.login().await;
+
+    let (status, body) = harness.get_json("/u/diagnostics/schema").await;
+    assert_eq!(status, Status::Ok);
+    assert_eq!(body, json!(null));
+
+    let harness = Harness::configured(MockConfig::default(), |figment| {
+        figment.merge(("schwab.strict_schema", true))
+    })
+    .await;
+    harness.login().await;
+
+    let (status, _) = harness.get_json("/u/quotes?symbols=AAPL,MSFT").await;
+    assert_eq!(status, Status::Ok);
+
+    // the mock sends nothing the schema doesn't know
+    let (status, body) = harness.get_json("/u/diagnostics/schema").await;
+    assert_eq!(status, Status::Ok);
+    assert_eq!(body, json!([]));
+}
+
+#[rocket::async_test]
+async fn invalid_symbols_are_rejected() {
+    let harness = Harness::start().await;
+    harness.login().await;
+
+    let (status, body) = harness
+        .get_json("/u/quotes?symbols=AAPL,AAPL%20%20251319C00200000")
+        .await;
+    assert_eq!(status, Status::BadRequest);
+    assert_eq!(
+        body,
+        json!({
+            "error": {
+                "InvalidSymbol": {
+                    "symbol": "AAPL  251319C00200000",
+                    "reason": "InvalidExpiry",
+                }
+            }
+        })
+    );
+}
+
+#[rocket::async_test]
+async fn quotes_are_fetched_in_chunks() {
+    let harness = Harness::start().await;
+    harness.login().await;
+
+    let symbols: Vec<String> = (0..mock_schwab::MAX_SYMBOLS * 2 + 100)
+        .map(|i| format!("S{i:04}"))
+        .collect();
+
+    let (status, body) = harness
+        .get_json(&format!("/u/quotes?symbols={}", symbols.join(",")))
+        .await;
+    assert_eq!(status, Status::Ok);
+
+    let quotes = body.as_object().unwrap();
+    assert_eq!(quotes.len(), symbols.len());
+    assert!(
+        symbols
+            .iter()
+            .all(|symbol| quotes[symbol]["symbol"] == *symbol)
+    );
+}
+
+#[rocket::async_test]
+async fn expired_access_tokens_are_refreshed() {
+    // well inside the backend's five minute refresh margin, so every request refreshes
+    let harness = Harness::with_mock(MockConfig {
+        access_token_lifetime: 60,
+        ..Default::default()
+    })
+    .await;
+    harness.login().await;
+
+    // the mock retires each refresh token once used, so this only keeps working
+    // if every rotated token is persisted to the session
+    for _ in 0..3 {
+        let (status, _) = harness.get_json("/u/user").await;
+        assert_eq!(status, Status::Ok);
+    }
+
+    let (status, body) = harness.get_json("/u/session").await;
+    assert_eq!(status, Status::Ok);
+    assert!(body["access_token_expires_in"].as_i64().unwrap() <= 60);
+    assert_eq!(body["reauthentication_required_soon"], false);
+}
+
+#[rocket::async_test]
+async fn failed_refresh_is_unauthorized() {
+    let harness = Harness::with_mock(MockConfig {
+        access_token_lifetime: 60,
+        ..Default::default()
+    })
+    .await;
+    harness.login().await;
+
+    harness
+        .inject(FaultRule {
+            fault: Fault::ServerError,
+            scope: Scope::OAuth,
+            count: None,
+        })
+        .await;
+
+    let (status, body) = harness.get_json("/u/user").await;
+    assert_eq!(status, Status::Unauthorized);
+    assert_eq!(body, json!({ "error": "InvalidCredentials" }));
+}
+
+#[rocket::async_test]
+async fn malformed_upstream_body() {
+    let harness = Harness::start().await;
+    harness.login().await;
+
+    harness
+        .inject(FaultRule {
+            fault: Fault::Malformed,
+            scope: Scope::Trader,
+            count: Some(1),
+        })
+        .await;
+
+    let (status, body) = harness.get_json("/u/user").await;
+    assert_eq!(status, Status::InternalServerError);
+    assert_eq!(body, json!({ "error": "InvalidJson" }));
+
+    let (status, _) = harness.get_json("/u/user").await;
+    assert_eq!(status, Status::Ok);
+}
+
+#[rocket::async_test]
+async fn upstream_errors() {
+    let harness = Harness::configured(MockConfig::default(), |figment| {
+        figment
+            .merge(("schwab.retry.max_attempts", 2))
+            .merge(("schwab.retry.base_delay_ms", 10))
+    })
+    .await;
+    harness.login().await;
+
+    // each fault outlasts both attempts, so retried ones report the attempts made
+    let cases = [
+        (
+            Fault::Unauthorized,
+            Status::Unauthorized,
+            json!("UpstreamUnauthorized"),
+        ),
+        (
+            Fault::RateLimited,
+            Status::TooManyRequests,
+            json!({ "Retried": { "attempts": 2, "error": "UpstreamRateLimited" } }),
+        ),
+        (
+            Fault::ServerError,
+            Status::BadGateway,
+            json!({
+                "Retried": {
+                    "attempts": 2,
+                    "error": { "UpstreamFailed": { "status": 500, "detail": "Internal error" } },
+                }
+            }),
+        ),
+    ];
+
+    for (fault, status, error) in cases {
+        harness
+            .inject(FaultRule {
+                fault,
+                scope: Scope::Trader,
+                count: Some(2),
+            })
+            .await;
+
+        let (actual, body) = harness.get_json("/u/user").await;
+        assert_eq!(actual, status, "{fault:?}");
+        assert_eq!(body, json!({ "error": error }), "{fault:?}");
+
+        // clear whatever the fault has left
+        harness.get_json("/u/user").await;
+    }
+
+    let (status, _) = harness.get_json("/u/user").await;
+    assert_eq!(status, Status::Ok);
+}
+
+#[rocket::async_test]
+async fn transient_failures_are_retried() {
+    let harness = Harness::start().await;
+    harness.login().await;
+
+    for fault in [Fault::ServerError, Fault::RateLimited] {
+        harness
+            .inject(FaultRule {
+                fault,
+                scope: Scope::Trader,
+                count: Some(1),
+            })
+            .await;
+
+        let (status, body) = harness.get_json("/u/user").await;
+        assert_eq!(status, Status::Ok, "{fault:?}: {body}");
+        assert_eq!(body[0]["account_number"], "12345678");
+    }
+}
+
+#[rocket::async_test]
+async fn rate_limited() {
+    let harness = Harness::configured(MockConfig::default(), |figment| {
+        figment
+            .merge(("schwab.rate_limit.trader.capacity", 2))
+            .merge(("schwab.rate_limit.trader.per_minute", 1))
+            .merge(("schwab.rate_limit.trader.max_wait_ms", 0))
+    })
+    .await;
+    harness.login().await;
+
+    for _ in 0..2 {
+        let (status, _) = harness.get_json("/u/user").await;
+        assert_eq!(status, Status::Ok);
+    }
+
+    let (status, body) = harness.get_json("/u/user").await;
+    assert_eq!(status, Status::TooManyRequests);
+    assert!(
+        body["error"]["RateLimited"]["retry_after_ms"]
+            .as_u64()
+            .unwrap()
+            > 0
+    );
+
+    let (status, body) = harness.get_json("/u/rate_limit").await;
+    assert_eq!(status, Status::Ok);
+    assert!(body["trader"]["available"].as_f64().unwrap() < 1.0);
+    assert_eq!(body["trader"]["capacity"], 2);
+    assert_eq!(body["market_data"]["capacity"], 20);
+}
+
+#[rocket::async_test]
+async fn logout_ends_the_session() {
+    let harness = Harness::start().await;
+    harness.login().await;
+
+    let response = harness.client.post("/u/logout").dispatch().await;
+    assert_eq!(response.status(), Status::Ok);
+
+    let (status, _) = harness.get_json("/u/user").await;
+    assert_eq!(status, Status::Unauthorized);
+}
@@ -0,0 +1,241 @@
+mod common;
+
+use std::time::Duration;
+
+use common::Harness;
+use mock_schwab::{Fault, FaultRule, MockConfig, Scope};
+use rocket::{
+    futures::{SinkExt, StreamExt},
+    tokio::{
+        net::TcpStream,
+        time::{sleep, timeout},
+    },
+};
+use serde_json::{Value, json};
+use tokio_tungstenite::{
+    MaybeTlsStream, WebSocketStream, connect_async,
+    tungstenite::{Message, client::IntoClientRequest, http::HeaderValue},
+};
+
+type Socket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// Comfortably longer than a poll interval.
+const RECV_TIMEOUT: Duration = Duration::from_secs(5);
+
+async fn connect(harness: &Harness) -> Socket {
+    connect_to(harness, "/u/quotes/stream").await
+}
+
+async fn connect_to(harness: &Harness, path: &str) -> Socket {
+    let port = harness.serve().await;
+    let cookie = harness.session_cookie().expect("should be logged in");
+
+    let mut request = format!("ws://127.0.0.1:{port}{path}")
+        .into_client_request()
+        .unwrap();
+    request.headers_mut().insert(
+        "Cookie",
+        HeaderValue::from_str(&format!("__Host-session={cookie}")).unwrap(),
+    );
+
+    let (socket, _) = connect_async(request)
+        .await
+        .expect("websocket upgrade should succeed");
+    socket
+}
+
+async fn send(socket: &mut Socket, message: Value) {
+    socket
+        .send(Message::Text(message.to_string()))
+        .await
+        .expect("send should succeed");
+}
+
+/// The next JSON text message, skipping anything else.
+async fn recv(socket: &mut Socket) -> Value {
+    loop {
+        let message = timeout(RECV_TIMEOUT, socket.next())
+            .await
+            .expect("should receive a message in time")
+            .expect("socket should stay open")
+            .expect("socket should not error");
+
+        if let Message::Text(text) = message {
+            return serde_json::from_str(&text).expect("messages should be JSON");
+        }
+    }
+}
+
+/// The next message of type `kind`, skipping quote updates and anything else.
+async fn recv_type(socket: &mut Socket, kind: &str) -> Value {
+    loop {
+        let message = recv(socket).await;
+        if message["type"] == kind {
+            return message;
+        }
+    }
+}
+
+#[rocket::async_test]
+async fn hello_and_acks() {
+    let harness = Harness::start().await;
+    harness.login().await;
+    let mut socket = connect(&harness).await;
+
+    let hello = recv(&mut socket).await;
+    assert_eq!(hello["type"], "hello", "{hello}");
+    assert_eq!((&hello["v"], &hello["protocol"]), (&json!(1), &json!(1)));
+    assert!(hello["session"]["refresh_token_expires_in"].is_i64());
+
+    let requests = [
+        json!({ "v": 1, "id": "1", "type": "subscribe", "symbols": ["AAPL"] }),
+        json!({ "v": 1, "id": "2", "type": "add", "symbols": ["MSFT", "AAPL"] }),
+        json!({ "v": 1, "id": "3", "type": "remove", "symbols": ["AAPL"] }),
+        json!({ "id": "4", "type": "ping" }),
+    ];
+    for request in requests {
+        send(&mut socket, request.clone()).await;
+        let ack = recv_type(&mut socket, "ack").await;
+        assert_eq!(ack["id"], request["id"]);
+    }
+
+    // updates are narrowed to the symbols watched when they arrive
+    let quotes = loop {
+        let update = recv_type(&mut socket, "update").await;
+        if update["quotes"].get("MSFT").is_some() {
+            break update["quotes"].clone();
+        }
+    };
+    assert!(quotes.get("AAPL").is_none(), "{quotes}");
+}
+
+#[rocket::async_test]
+async fn normalized_stream() {
+    let harness = Harness::start().await;
+    harness.login().await;
+    let mut socket = connect_to(&harness, "/u/quotes/stream?format=normalized").await;
+
+    send(
+        &mut socket,
+        json!({ "type": "subscribe", "symbols": ["AAPL"] }),
+    )
+    .await;
+
+    // the first tick may predate the subscription
+    let aapl = loop {
+        let update = recv_type(&mut socket, "update").await;
+        let quotes = &update["quotes"];
+        assert!(quotes["invalid_symbols"].is_array(), "{quotes}");
+        if let Some(aapl) = quotes["quotes"].get("AAPL") {
+            break aapl.clone();
+        }
+    };
+    assert_eq!(aapl["asset_class"], "equity", "{aapl}");
+    assert!(aapl["mark"].as_f64().is_some());
+}
+
+#[rocket::async_test]
+async fn invalid_messages_are_nacked() {
+    let harness = Harness::start().await;
+    harness.login().await;
+    let mut socket = connect(&harness).await;
+
+    let cases = [
+        (
+            json!({ "id": "a", "type": "unsubscribe_all" }),
+            "invalid_message",
+        ),
+        (
+            json!({ "id": "b", "type": "add", "symbols": ["NOT A SYMBOL!"] }),
+            "invalid_message",
+        ),
+        (
+            json!({ "v": 2, "id": "c", "type": "ping" }),
+            "unsupported_version",
+        ),
+    ];
+
+    for (request, code) in cases {
+        send(&mut socket, request.clone()).await;
+
+        let nack = recv_type(&mut socket, "nack").await;
+        assert_eq!(nack["id"], request["id"]);
+        assert_eq!(nack["error"]["code"], code, "{nack}");
+        assert!(nack["error"]["message"].is_string());
+    }
+}
+
+#[rocket::async_test]
+async fn outage_sends_stale_snapshot() {
+    let harness = Harness::configured(MockConfig::default(), |figment| {
+        figment
+            .merge(("schwab.retry.max_attempts", 1))
+            .merge(("schwab.circuit_breaker.failure_threshold", 1))
+            .merge(("schwab.circuit_breaker.open_ms", 200))
+    })
+    .await;
+    harness.login().await;
+    let mut socket = connect(&harness).await;
+
+    send(
+        &mut socket,
+        json!({ "type": "subscribe", "symbols": ["AAPL"] }),
+    )
+    .await;
+    recv_type(&mut socket, "ack").await;
+    let mut good = recv_type(&mut socket, "update").await["quotes"].take();
+
+    harness
+        .inject(FaultRule {
+            fault: Fault::ServerError,
+            scope: Scope::MarketData,
+            count: None,
+        })
+        .await;
+
+    // updates polled before the fault may still arrive first
+    let status = loop {
+        let message = recv(&mut socket).await;
+        match message["type"].as_str() {
+            Some("update") => good = message["quotes"].clone(),
+            _ => break message,
+        }
+    };
+    assert_eq!(status["type"], "status", "{status}");
+    assert_eq!(status["status"], "upstream_unavailable", "{status}");
+
+    let snapshot = recv(&mut socket).await;
+    assert_eq!(snapshot["type"], "snapshot");
+    assert_eq!(snapshot["stale"], true);
+    assert_eq!(snapshot["resync"], false);
+    assert_eq!(snapshot["quotes"], good);
+
+    // failed probes stay quiet; the next messages announce the recovery and resume updates
+    sleep(Duration::from_millis(500)).await;
+    harness.clear_faults().await;
+
+    let status = recv(&mut socket).await;
+    assert_eq!(status["status"], "upstream_recovered", "{status}");
+
+    let live = recv(&mut socket).await;
+    assert_eq!(live["type"], "update", "{live}");
+}
+
+#[rocket::async_test]
+async fn schema_is_published() {
+    let harness = Harness::start().await;
+
+    let (status, body) = harness.get_json("/u/quotes/stream/schema").await;
+    assert_eq!(status, rocket::http::Status::Ok);
+    assert_eq!(body["client"]["title"], "ClientMessage");
+    assert_eq!(body["server"]["title"], "ServerMessage");
+}
+
+#[rocket::async_test]
+async fn requires_login() {
+    let harness = Harness::start().await;
+    let port = harness.serve().await;
+
+    let rejected = connect_async(format!("ws://127.0.0.1:{port}/u/quotes/stream")).await;
+    assert!(rejected.is_err());
+}
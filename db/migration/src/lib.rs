@@ -5,7 +5,11 @@ pub struct Migrator;
 #[async_trait::async_trait]
 impl MigratorTrait for Migrator {
     fn migrations() -> Vec<Box<dyn MigrationTrait>> {
-        vec![Box::new(m20260106_202619_create_user::Migration)]
+        vec![
+            Box::new(m20260106_202619_create_user::Migration),
+            Box::new(m20261018_140312_create_sessions::Migration),
+        ]
     }
 }
 mod m20260106_202619_create_user;
+mod m20261018_140312_create_sessions;
@@ -1 +1,12 @@
-// todo
+pub mod entity;
+pub mod sessions;
+
+use migration::{Migrator, MigratorTrait};
+pub use sea_orm::{DatabaseConnection, DbErr};
+
+/// Connect to `url` and bring the schema up to date.
+pub async fn connect(url: &str) -> Result<DatabaseConnection, DbErr> {
+    let db = sea_orm::Database::connect(url).await?;
+    Migrator::up(&db, None).await?;
+    Ok(db)
+}
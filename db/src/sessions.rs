@@ -0,0 +1,105 @@
+use chrono::{DateTime, Utc};
+use sea_orm::{
+    ActiveValue::Set, ColumnTrait, DatabaseConnection, DbErr, EntityTrait, QueryFilter,
+    sea_query::Expr,
+};
+
+use crate::entity::session;
+
+/// Look up the encrypted credentials stored for session `id`, unless it was last
+/// written before `stale_before`.
+pub async fn find(
+    db: &DatabaseConnection,
+    id: &str,
+    stale_before: DateTime<Utc>,
+) -> Result<Option<Vec<u8>>, DbErr> {
+    let session = session::Entity::find_by_id(id)
+        .filter(session::Column::UpdatedAt.gte(stale_before))
+        .one(db)
+        .await?;
+    Ok(session.map(|s| s.credentials))
+}
+
+/// Store the encrypted credentials for a new session `id`.
+pub async fn insert(db: &DatabaseConnection, id: &str, credentials: Vec<u8>) -> Result<(), DbErr> {
+    let now = chrono::Utc::now();
+
+    let model = session::ActiveModel {
+        id: Set(id.to_owned()),
+        credentials: Set(credentials),
+        created_at: Set(now),
+        updated_at: Set(now),
+    };
+
+    session::Entity::insert(model).exec(db).await?;
+
+    Ok(())
+}
+
+/// Overwrite the encrypted credentials for session `id`.
+///
+/// Never brings back a deleted session: returns `false` if there was no row to update.
+pub async fn update(
+    db: &DatabaseConnection,
+    id: &str,
+    credentials: Vec<u8>,
+) -> Result<bool, DbErr> {
+    let result = session::Entity::update_many()
+        .col_expr(session::Column::Credentials, Expr::value(credentials))
+        .col_expr(session::Column::UpdatedAt, Expr::value(chrono::Utc::now()))
+        .filter(session::Column::Id.eq(id))
+        .exec(db)
+        .await?;
+
+    Ok(result.rows_affected > 0)
+}
+
+pub async fn delete(db: &DatabaseConnection, id: &str) -> Result<(), DbErr> {
+    session::Entity::delete_by_id(id).exec(db).await?;
+    Ok(())
+}
+
+/// Delete every session last written before `stale_before`, returning how many there were.
+pub async fn prune(db: &DatabaseConnection, stale_before: DateTime<Utc>) -> Result<u64, DbErr> {
+    let result = session::Entity::delete_many()
+        .filter(session::Column::UpdatedAt.lt(stale_before))
+        .exec(db)
+        .await?;
+    Ok(result.rows_affected)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeDelta;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn stale_sessions_are_hidden_and_pruned() {
+        let db = crate::connect("sqlite::memory:").await.unwrap();
+        insert(&db, "old", vec![1]).await.unwrap();
+
+        let before_write = Utc::now() - TimeDelta::hours(1);
+        let after_write = Utc::now() + TimeDelta::hours(1);
+
+        assert_eq!(find(&db, "old", before_write).await.unwrap(), Some(vec![1]));
+        assert_eq!(find(&db, "old", after_write).await.unwrap(), None);
+
+        assert_eq!(prune(&db, before_write).await.unwrap(), 0);
+        assert_eq!(prune(&db, after_write).await.unwrap(), 1);
+        assert_eq!(find(&db, "old", before_write).await.unwrap(), None);
+    }
+    #[tokio::test]
+    async fn updates_never_revive_deleted_sessions() {
+        let db = crate::connect("sqlite::memory:").await.unwrap();
+        let long_ago = Utc::now() - TimeDelta::days(1);
+
+        insert(&db, "session", vec![1]).await.unwrap();
+        assert!(update(&db, "session", vec![2]).await.unwrap());
+        assert_eq!(find(&db, "session", long_ago).await.unwrap(), Some(vec![2]));
+
+        delete(&db, "session").await.unwrap();
+        assert!(!update(&db, "session", vec![3]).await.unwrap());
+        assert_eq!(find(&db, "session", long_ago).await.unwrap(), None);
+    }
+}
@@ -0,0 +1,37 @@
+//! Routes for steering the mock from tests and scripts. Not part of any Schwab API.
+
+use rocket::{Route, State, http::Status, serde::json::Json};
+
+use crate::{
+    faults::{FaultRule, Faults},
+    oauth::Tokens,
+};
+
+#[get("/faults")]
+fn list_faults(faults: &State<Faults>) -> Json<Vec<FaultRule>> {
+    Json(faults.list())
+}
+
+/// Add a fault rule, e.g. `{"fault": "rate_limited", "scope": "market_data", "count": 3}`.
+#[post("/faults", data = "<rule>")]
+fn add_fault(faults: &State<Faults>, rule: Json<FaultRule>) -> Status {
+    faults.push(rule.into_inner());
+    Status::NoContent
+}
+
+#[delete("/faults")]
+fn clear_faults(faults: &State<Faults>) -> Status {
+    faults.clear();
+    Status::NoContent
+}
+
+/// Expire every access token handed out so far, so the next API call needs a refresh.
+#[post("/tokens/expire")]
+fn expire_tokens(tokens: &State<Tokens>) -> Status {
+    tokens.expire_access_tokens();
+    Status::NoContent
+}
+
+pub fn routes() -> Vec<Route> {
+    routes![list_faults, add_fault, clear_faults, expire_tokens]
+}
@@ -0,0 +1,124 @@
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+
+use crate::MockError;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counted_faults_run_out() {
+        let faults = Faults::new(vec![FaultRule {
+            fault: Fault::RateLimited,
+            scope: Scope::MarketData,
+            count: Some(2),
+        }]);
+
+        assert!(faults.check(Scope::Trader).is_ok());
+        assert!(matches!(
+            faults.check(Scope::MarketData),
+            Err(MockError::Fault(Fault::RateLimited))
+        ));
+        assert!(faults.check(Scope::MarketData).is_err());
+        assert!(faults.check(Scope::MarketData).is_ok());
+        assert!(faults.list().is_empty());
+    }
+
+    #[test]
+    fn uncounted_faults_stay() {
+        let faults = Faults::new(vec![FaultRule {
+            fault: Fault::Malformed,
+            scope: Scope::All,
+            count: None,
+        }]);
+
+        for scope in [Scope::OAuth, Scope::Trader, Scope::MarketData] {
+            assert!(faults.check(scope).is_err());
+        }
+
+        faults.clear();
+        assert!(faults.check(Scope::Trader).is_ok());
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Fault {
+    /// 401 with Schwab's error body
+    Unauthorized,
+    /// 429 with `Retry-After`
+    RateLimited,
+    /// 500 with Schwab's error body
+    ServerError,
+    /// 200 with a truncated JSON body
+    Malformed,
+}
+
+/// Which API a fault applies to.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Scope {
+    #[default]
+    All,
+    #[serde(rename = "oauth")]
+    OAuth,
+    Trader,
+    MarketData,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FaultRule {
+    pub fault: Fault,
+    #[serde(default)]
+    pub scope: Scope,
+    /// how many responses to break before the rule goes away; forever if unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub count: Option<u32>,
+}
+
+/// The active fault rules. The first rule matching a request wins.
+#[derive(Debug, Default)]
+pub struct Faults(Mutex<Vec<FaultRule>>);
+
+impl Faults {
+    pub fn new(rules: Vec<FaultRule>) -> Self {
+        Self(Mutex::new(rules))
+    }
+
+    /// Fail with the first fault that applies to `scope`, using it up if it is counted.
+    pub fn check(&self, scope: Scope) -> Result<(), MockError> {
+        let mut rules = self.0.lock().expect("faults lock poisoned");
+
+        let Some(i) = rules
+            .iter()
+            .position(|rule| rule.scope == Scope::All || rule.scope == scope)
+        else {
+            return Ok(());
+        };
+
+        let fault = rules[i].fault;
+
+        if let Some(count) = &mut rules[i].count {
+            *count = count.saturating_sub(1);
+            if *count == 0 {
+                rules.remove(i);
+            }
+        }
+
+        Err(MockError::Fault(fault))
+    }
+
+    pub fn push(&self, rule: FaultRule) {
+        self.0.lock().expect("faults lock poisoned").push(rule);
+    }
+
+    pub fn list(&self) -> Vec<FaultRule> {
+        self.0.lock().expect("faults lock poisoned").clone()
+    }
+
+    pub fn clear(&self) {
+        self.0.lock().expect("faults lock poisoned").clear();
+    }
+}
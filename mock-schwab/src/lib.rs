@@ -0,0 +1,173 @@
+//! A stand-in for the Schwab OAuth, trader and market data APIs.
+//!
+//! Serves just enough of each API for the backend to log in, look up the user,
+//! list their orders, poll quotes and chart price history without real
+//! credentials. Failures can be injected through
+//! the `faults` config key or at runtime through the `/_mock` control routes.
+
+#[macro_use]
+extern crate rocket;
+
+mod control;
+mod faults;
+mod market;
+mod oauth;
+mod trader;
+
+use rocket::{
+    Build, Request, Rocket,
+    fairing::AdHoc,
+    figment::{
+        Figment,
+        providers::{Env, Serialized},
+    },
+    http::{ContentType, Header, Status},
+    response::{self, Responder, Response},
+    serde::json::json,
+};
+use serde::{Deserialize, Serialize};
+
+pub use faults::{Fault, FaultRule, Scope};
+pub use market::MAX_SYMBOLS;
+
+/// Port the mock listens on unless `MOCK_SCHWAB_PORT` says otherwise.
+pub const DEFAULT_PORT: u16 = 8001;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MockConfig {
+    /// seeds the quote random walk
+    pub seed: u64,
+    /// seconds until an issued access token stops working
+    pub access_token_lifetime: u64,
+    /// the number reported for the one brokerage account
+    pub account_number: String,
+    /// faults active from startup
+    pub faults: Vec<FaultRule>,
+}
+
+impl Default for MockConfig {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            access_token_lifetime: 1800,
+            account_number: "12345678".to_owned(),
+            faults: vec![],
+        }
+    }
+}
+
+/// Rocket's defaults on [`DEFAULT_PORT`], overridable with `MOCK_SCHWAB_` environment
+/// variables so they don't collide with the backend's `ROCKET_` ones.
+pub fn figment() -> Figment {
+    Figment::from(rocket::Config::default())
+        .merge(Serialized::default("port", DEFAULT_PORT))
+        .merge(Env::prefixed("MOCK_SCHWAB_").global())
+}
+
+pub fn rocket() -> Rocket<Build> {
+    build(figment())
+}
+
+/// The mock server, configured from `figment`.
+pub fn build(figment: Figment) -> Rocket<Build> {
+    rocket::custom(figment)
+        .mount("/v1/oauth", oauth::routes())
+        .mount("/trader/v1", trader::routes())
+        .mount("/marketdata/v1", market::routes())
+        .mount("/_mock", control::routes())
+        .attach(AdHoc::try_on_ignite("Mock Schwab", |rocket| async {
+            let config = match rocket.figment().extract::<MockConfig>() {
+                Ok(config) => config,
+                Err(e) => {
+                    error!("invalid mock configuration: {e}");
+                    return Err(rocket);
+                }
+            };
+
+            Ok(rocket
+                .manage(oauth::Tokens::new(config.access_token_lifetime))
+                .manage(market::Market::new(config.seed))
+                .manage(faults::Faults::new(config.faults.clone()))
+                .manage(config))
+        }))
+}
+
+/// Body served for [`Fault::Malformed`]: cut off mid-object.
+const MALFORMED_BODY: &str = r#"{"errors":[{"id":"0","status":"20"#;
+
+/// Every way a mock endpoint can fail.
+#[derive(Debug)]
+pub enum MockError {
+    /// an injected fault
+    Fault(Fault),
+    /// missing, unknown or expired bearer token
+    Unauthorized,
+    /// a request Schwab would reject, in the API's `errors` format
+    BadRequest {
+        detail: String,
+        pointers: Vec<String>,
+    },
+    /// an OAuth error, in the RFC 6749 format
+    OAuth {
+        status: Status,
+        error: &'static str,
+        description: String,
+    },
+}
+
+fn api_error(status: Status, detail: &str, pointers: &[String]) -> String {
+    let mut error = json!({
+        "id": format!("{:032x}", rand::random::<u128>()),
+        "status": status.code.to_string(),
+        "title": status.reason_lossy(),
+        "detail": detail,
+    });
+
+    if !pointers.is_empty() {
+        error["source"] = json!({ "pointer": pointers });
+    }
+
+    json!({ "errors": [error] }).to_string()
+}
+
+impl<'r> Responder<'r, 'static> for MockError {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        let (status, body) = match self {
+            Self::Fault(Fault::Malformed) => (Status::Ok, MALFORMED_BODY.to_owned()),
+            Self::Fault(Fault::Unauthorized) | Self::Unauthorized => (
+                Status::Unauthorized,
+                api_error(Status::Unauthorized, "Invalid or expired access token", &[]),
+            ),
+            Self::Fault(Fault::RateLimited) => (
+                Status::TooManyRequests,
+                api_error(Status::TooManyRequests, "Too many requests", &[]),
+            ),
+            Self::Fault(Fault::ServerError) => (
+                Status::InternalServerError,
+                api_error(Status::InternalServerError, "Internal error", &[]),
+            ),
+            Self::BadRequest { detail, pointers } => (
+                Status::BadRequest,
+                api_error(Status::BadRequest, &detail, &pointers),
+            ),
+            Self::OAuth {
+                status,
+                error,
+                description,
+            } => (
+                status,
+                json!({ "error": error, "error_description": description }).to_string(),
+            ),
+        };
+
+        let mut response = Response::build_from(body.respond_to(request)?);
+        response.status(status).header(ContentType::JSON);
+
+        if status == Status::TooManyRequests {
+            response.header(Header::new("Retry-After", "1"));
+        }
+
+        response.ok()
+    }
+}
@@ -0,0 +1,396 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use rand::{Rng, SeedableRng, rngs::StdRng};
+use rocket::{
+    Route, State,
+    serde::json::{Json, Value, json},
+};
+
+use crate::{
+    MockError,
+    faults::{Faults, Scope},
+    oauth::{Bearer, Tokens},
+};
+
+/// Largest move of a single step, as a fraction of the price.
+const MAX_STEP: f64 = 0.002;
+
+/// Most symbols one quotes request may ask for.
+pub const MAX_SYMBOLS: usize = 500;
+
+/// The sections `fields` can ask for; all of them by default.
+const FIELDS: [&str; 5] = ["quote", "fundamental", "extended", "reference", "regular"];
+
+fn now_millis() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("clock should be after the epoch")
+        .as_millis() as i64
+}
+
+fn round_cents(price: f64) -> f64 {
+    (price * 100.0).round() / 100.0
+}
+
+/// Anything Schwab might call a symbol: equities, `$INDEX`es, `/FUTURES` and
+/// space-padded OCC option symbols.
+fn is_valid_symbol(symbol: &str) -> bool {
+    !symbol.is_empty()
+        && symbol.len() <= 21
+        && symbol
+            .chars()
+            .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || " ./$-_".contains(c))
+}
+
+#[derive(Debug)]
+struct Walk {
+    ssid: i64,
+    close: f64,
+    open: f64,
+    last: f64,
+    high: f64,
+    low: f64,
+    week_52_high: f64,
+    week_52_low: f64,
+    volume: i64,
+}
+
+impl Walk {
+    fn new(rng: &mut StdRng) -> Self {
+        let close = round_cents(rng.gen_range(10.0..500.0));
+
+        Self {
+            ssid: rng.gen_range(1_000_000..2_000_000_000),
+            close,
+            open: close,
+            last: close,
+            high: close,
+            low: close,
+            week_52_high: round_cents(close * rng.gen_range(1.05..1.6)),
+            week_52_low: round_cents(close * rng.gen_range(0.5..0.95)),
+            volume: 0,
+        }
+    }
+
+    fn step(&mut self, rng: &mut StdRng) {
+        self.last = round_cents(self.last * (1.0 + rng.gen_range(-MAX_STEP..MAX_STEP)));
+        self.high = self.high.max(self.last);
+        self.low = self.low.min(self.last);
+        self.week_52_high = self.week_52_high.max(self.last);
+        self.week_52_low = self.week_52_low.min(self.last);
+        self.volume += 100 * rng.gen_range(1..50);
+    }
+
+    /// This walk as a Schwab equity quote, limited to the requested `fields`.
+    fn to_json(&self, symbol: &str, fields: &[&str]) -> Value {
+        let now = now_millis();
+        let net_change = round_cents(self.last - self.close);
+        let percent_change = net_change / self.close * 100.0;
+
+        let mut quote = json!({
+            "assetMainType": "EQUITY",
+            "assetSubType": "COE",
+            "quoteType": "NBBO",
+            "realtime": true,
+            "ssid": self.ssid,
+            "symbol": symbol,
+        });
+
+        let sections = [
+            (
+                "quote",
+                json!({
+                    "52WeekHigh": self.week_52_high,
+                    "52WeekLow": self.week_52_low,
+                    "askMicId": "ARCX",
+                    "askPrice": round_cents(self.last + 0.01),
+                    "askSize": 200,
+                    "askTime": now,
+                    "bidMicId": "ARCX",
+                    "bidPrice": round_cents(self.last - 0.01),
+                    "bidSize": 300,
+                    "bidTime": now,
+                    "closePrice": self.close,
+                    "highPrice": self.high,
+                    "lastMicId": "XADF",
+                    "lastPrice": self.last,
+                    "lastSize": 100,
+                    "lowPrice": self.low,
+                    "mark": self.last,
+                    "markChange": net_change,
+                    "markPercentChange": percent_change,
+                    "netChange": net_change,
+                    "netPercentChange": percent_change,
+                    "openPrice": self.open,
+                    "quoteTime": now,
+                    "securityStatus": "Normal",
+                    "totalVolume": self.volume,
+                    "tradeTime": now,
+                    "volatility": 0.0123,
+                }),
+            ),
+            (
+                "fundamental",
+                json!({
+                    "avg10DaysVolume": 1_000_000.0,
+                    "avg1YearVolume": 1_250_000.0,
+                    "divAmount": 1.0,
+                    "divYield": round_cents(100.0 / self.close),
+                    "eps": 4.2,
+                    "peRatio": round_cents(self.close / 4.2),
+                }),
+            ),
+            (
+                "extended",
+                json!({
+                    "askPrice": 0.0,
+                    "askSize": 0,
+                    "bidPrice": 0.0,
+                    "bidSize": 0,
+                    "lastPrice": self.close,
+                    "lastSize": 0,
+                    "mark": 0.0,
+                    "quoteTime": 0,
+                    "totalVolume": 0,
+                    "tradeTime": 0,
+                }),
+            ),
+            (
+                "reference",
+                json!({
+                    "cusip": format!("{:09}", self.ssid % 1_000_000_000),
+                    "description": format!("{symbol} Mock Corp"),
+                    "exchange": "Q",
+                    "exchangeName": "NASDAQ",
+                    "isHardToBorrow": false,
+                    "isShortable": true,
+                }),
+            ),
+            (
+                "regular",
+                json!({
+                    "regularMarketLastPrice": self.last,
+                    "regularMarketLastSize": 100,
+                    "regularMarketNetChange": net_change,
+                    "regularMarketPercentChange": percent_change,
+                    "regularMarketTradeTime": now,
+                }),
+            ),
+        ];
+
+        for (name, section) in sections {
+            if fields.contains(&name) {
+                quote[name] = section;
+            }
+        }
+
+        quote
+    }
+}
+
+/// Synthetic prices: every symbol takes one random step per quote request.
+#[derive(Debug)]
+pub struct Market {
+    state: Mutex<(StdRng, HashMap<String, Walk>)>,
+}
+
+impl Market {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            state: Mutex::new((StdRng::seed_from_u64(seed), HashMap::new())),
+        }
+    }
+
+    fn quotes(&self, symbols: &[&str], fields: &[&str]) -> Value {
+        let mut state = self.state.lock().expect("market lock poisoned");
+        let (rng, walks) = &mut *state;
+
+        let mut response = json!({});
+        let mut invalid = vec![];
+
+        for &symbol in symbols {
+            if !is_valid_symbol(symbol) {
+                invalid.push(symbol);
+                continue;
+            }
+
+            let walk = walks
+                .entry(symbol.to_owned())
+                .or_insert_with(|| Walk::new(rng));
+            walk.step(rng);
+
+            response[symbol] = walk.to_json(symbol, fields);
+        }
+
+        if !invalid.is_empty() {
+            response["errors"] = json!({ "invalidSymbols": invalid });
+        }
+
+        response
+    }
+
+    /// `count` candles `width_ms` apart up to now, starting from the close of
+    /// `symbol`'s walk without moving it.
+    fn history(&self, symbol: &str, count: i64, width_ms: i64) -> Value {
+        let mut state = self.state.lock().expect("market lock poisoned");
+        let (rng, walks) = &mut *state;
+
+        let walk = walks
+            .entry(symbol.to_owned())
+            .or_insert_with(|| Walk::new(rng));
+
+        let end = now_millis() / width_ms * width_ms;
+        let mut price = walk.close;
+
+        let candles: Vec<Value> = (0..count)
+            .map(|i| {
+                let open = price;
+                let (mut high, mut low) = (open, open);
+
+                for _ in 0..4 {
+                    price = round_cents(price * (1.0 + rng.gen_range(-MAX_STEP..MAX_STEP)));
+                    high = high.max(price);
+                    low = low.min(price);
+                }
+
+                json!({
+                    "open": open,
+                    "high": high,
+                    "low": low,
+                    "close": price,
+                    "volume": 100 * rng.gen_range(1..500),
+                    "datetime": end - width_ms * (count - i),
+                })
+            })
+            .collect();
+
+        json!({
+            "symbol": symbol,
+            "empty": candles.is_empty(),
+            "previousClose": walk.close,
+            "candles": candles,
+        })
+    }
+}
+
+#[get("/quotes?<symbols>&<fields>")]
+fn quotes(
+    faults: &State<Faults>,
+    tokens: &State<Tokens>,
+    market: &State<Market>,
+    bearer: Bearer,
+    symbols: Option<&str>,
+    fields: Option<&str>,
+) -> Result<Json<Value>, MockError> {
+    faults.check(Scope::MarketData)?;
+    tokens.check(&bearer)?;
+
+    let symbols: Vec<&str> = symbols
+        .into_iter()
+        .flat_map(|symbols| symbols.split(','))
+        .filter(|symbol| !symbol.is_empty())
+        .collect();
+
+    if symbols.len() > MAX_SYMBOLS {
+        return Err(MockError::BadRequest {
+            detail: format!("A maximum of {MAX_SYMBOLS} symbols can be requested at once."),
+            pointers: vec!["/data/attributes/symbols".to_owned()],
+        });
+    }
+
+    if symbols.is_empty() {
+        return Err(MockError::BadRequest {
+            detail: "Search combination should have min of 1.".to_owned(),
+            pointers: vec![
+                "/data/attributes/symbols".to_owned(),
+                "/data/attributes/cusips".to_owned(),
+                "/data/attributes/ssids".to_owned(),
+            ],
+        });
+    }
+
+    let fields: Vec<&str> = match fields {
+        Some(fields) if fields != "all" => fields.split(',').collect(),
+        _ => FIELDS.to_vec(),
+    };
+
+    Ok(Json(market.quotes(&symbols, &fields)))
+}
+
+#[derive(FromForm)]
+struct HistoryQuery<'r> {
+    symbol: Option<&'r str>,
+    #[field(name = "periodType")]
+    period_type: Option<&'r str>,
+    period: Option<i64>,
+    #[field(name = "frequencyType")]
+    frequency_type: Option<&'r str>,
+    frequency: Option<i64>,
+}
+
+#[get("/pricehistory?<query..>")]
+fn price_history(
+    faults: &State<Faults>,
+    tokens: &State<Tokens>,
+    market: &State<Market>,
+    bearer: Bearer,
+    query: HistoryQuery<'_>,
+) -> Result<Json<Value>, MockError> {
+    faults.check(Scope::MarketData)?;
+    tokens.check(&bearer)?;
+
+    let bad_request = |detail: &str, field: &str| MockError::BadRequest {
+        detail: detail.to_owned(),
+        pointers: vec![format!("/data/attributes/{field}")],
+    };
+
+    let symbol = query
+        .symbol
+        .filter(|symbol| is_valid_symbol(symbol))
+        .ok_or_else(|| bad_request("A valid symbol is required.", "symbol"))?;
+
+    let period_type = query.period_type.unwrap_or("day");
+    let days_per_period = match period_type {
+        "day" => 1,
+        "month" => 30,
+        "year" => 365,
+        _ => return Err(bad_request("Invalid periodType.", "periodType")),
+    };
+    let period = query
+        .period
+        .unwrap_or(if period_type == "day" { 10 } else { 1 });
+
+    let frequency_type = query.frequency_type.unwrap_or(if period_type == "day" {
+        "minute"
+    } else {
+        "daily"
+    });
+    let minutes_per_frequency = match frequency_type {
+        "minute" => 1,
+        "daily" => 24 * 60,
+        "weekly" => 7 * 24 * 60,
+        "monthly" => 30 * 24 * 60,
+        _ => return Err(bad_request("Invalid frequencyType.", "frequencyType")),
+    };
+    let frequency = query.frequency.unwrap_or(1);
+
+    if period < 1 || frequency < 1 {
+        return Err(bad_request(
+            "period and frequency must be positive.",
+            "period",
+        ));
+    }
+
+    let width_minutes = minutes_per_frequency * frequency;
+    let count = period * days_per_period * 24 * 60 / width_minutes;
+
+    Ok(Json(market.history(symbol, count, width_minutes * 60_000)))
+}
+
+pub fn routes() -> Vec<Route> {
+    routes![quotes, price_history]
+}
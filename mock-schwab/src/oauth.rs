@@ -0,0 +1,280 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use rand::{Rng, distributions::Alphanumeric};
+use rocket::{
+    Request, Route, State,
+    form::Form,
+    http::{RawStr, Status},
+    request::{FromRequest, Outcome},
+    response::Redirect,
+    serde::json::Json,
+};
+use serde::Serialize;
+
+use crate::{
+    MockError,
+    faults::{Faults, Scope},
+};
+
+fn random_token(len: usize) -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(len)
+        .map(char::from)
+        .collect()
+}
+
+#[derive(Debug, Serialize)]
+pub struct TokenResponse {
+    access_token: String,
+    refresh_token: String,
+    token_type: &'static str,
+    expires_in: u64,
+    scope: &'static str,
+    id_token: String,
+}
+
+#[derive(Debug, Default)]
+struct Issued {
+    codes: HashSet<String>,
+    access: HashMap<String, Instant>,
+    refresh: HashSet<String>,
+}
+
+/// Authorization codes, access tokens and refresh tokens handed out so far.
+///
+/// Like Schwab, refreshing rotates the refresh token and retires the old one.
+#[derive(Debug)]
+pub struct Tokens {
+    lifetime: Duration,
+    issued: Mutex<Issued>,
+}
+
+impl Tokens {
+    pub fn new(lifetime_seconds: u64) -> Self {
+        Self {
+            lifetime: Duration::from_secs(lifetime_seconds),
+            issued: Mutex::default(),
+        }
+    }
+
+    fn issue_code(&self) -> String {
+        let code = random_token(32);
+        self.issued
+            .lock()
+            .expect("tokens lock poisoned")
+            .codes
+            .insert(code.clone());
+        code
+    }
+
+    fn issue(issued: &mut Issued, lifetime: Duration) -> TokenResponse {
+        let access_token = random_token(48);
+        let refresh_token = random_token(48);
+
+        issued
+            .access
+            .insert(access_token.clone(), Instant::now() + lifetime);
+        issued.refresh.insert(refresh_token.clone());
+
+        TokenResponse {
+            access_token,
+            refresh_token,
+            token_type: "Bearer",
+            expires_in: lifetime.as_secs(),
+            scope: "api",
+            id_token: random_token(64),
+        }
+    }
+
+    fn exchange_code(&self, code: &str) -> Option<TokenResponse> {
+        let mut issued = self.issued.lock().expect("tokens lock poisoned");
+
+        // codes are single use
+        if !issued.codes.remove(code) {
+            return None;
+        }
+
+        Some(Self::issue(&mut issued, self.lifetime))
+    }
+
+    fn exchange_refresh_token(&self, refresh_token: &str) -> Option<TokenResponse> {
+        let mut issued = self.issued.lock().expect("tokens lock poisoned");
+
+        if !issued.refresh.remove(refresh_token) {
+            return None;
+        }
+
+        Some(Self::issue(&mut issued, self.lifetime))
+    }
+
+    fn revoke(&self, token: &str) {
+        let mut issued = self.issued.lock().expect("tokens lock poisoned");
+        issued.access.remove(token);
+        issued.refresh.remove(token);
+    }
+
+    /// Make every outstanding access token stale, forcing clients to refresh.
+    pub fn expire_access_tokens(&self) {
+        let now = Instant::now();
+        let mut issued = self.issued.lock().expect("tokens lock poisoned");
+
+        for expiry in issued.access.values_mut() {
+            *expiry = now;
+        }
+    }
+
+    pub fn check(&self, bearer: &Bearer) -> Result<(), MockError> {
+        let issued = self.issued.lock().expect("tokens lock poisoned");
+
+        match bearer
+            .0
+            .as_deref()
+            .and_then(|token| issued.access.get(token))
+        {
+            Some(expiry) if Instant::now() < *expiry => Ok(()),
+            _ => Err(MockError::Unauthorized),
+        }
+    }
+}
+
+/// The bearer token on a request, if any. Checked by [`Tokens::check`] so that
+/// rejections use Schwab's error body rather than a catcher's.
+pub struct Bearer(Option<String>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Bearer {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let token = request
+            .headers()
+            .get_one("Authorization")
+            .and_then(|header| header.strip_prefix("Bearer "))
+            .map(str::to_owned);
+
+        Outcome::Success(Self(token))
+    }
+}
+
+/// Approves every request immediately, as if the user logged in and consented.
+#[get("/authorize?<redirect_uri>&<state>&<response_type>")]
+fn authorize(
+    faults: &State<Faults>,
+    tokens: &State<Tokens>,
+    redirect_uri: &str,
+    state: Option<&str>,
+    response_type: &str,
+) -> Result<Redirect, MockError> {
+    faults.check(Scope::OAuth)?;
+
+    if response_type != "code" {
+        return Err(MockError::OAuth {
+            status: Status::BadRequest,
+            error: "unsupported_response_type",
+            description: format!("unsupported response_type {response_type}"),
+        });
+    }
+
+    let mut location = format!("{redirect_uri}?code={}", tokens.issue_code());
+    if let Some(state) = state {
+        location.push_str("&state=");
+        location.push_str(RawStr::new(state).percent_encode().as_str());
+    }
+
+    Ok(Redirect::to(location))
+}
+
+#[derive(FromForm)]
+struct TokenRequest<'r> {
+    grant_type: &'r str,
+    code: Option<&'r str>,
+    refresh_token: Option<&'r str>,
+}
+
+fn invalid_grant(description: &str) -> MockError {
+    MockError::OAuth {
+        status: Status::BadRequest,
+        error: "invalid_grant",
+        description: description.to_owned(),
+    }
+}
+
+#[post("/token", data = "<form>")]
+fn token(
+    faults: &State<Faults>,
+    tokens: &State<Tokens>,
+    client: ClientCredentials,
+    form: Form<TokenRequest<'_>>,
+) -> Result<Json<TokenResponse>, MockError> {
+    faults.check(Scope::OAuth)?;
+
+    if !client.0 {
+        return Err(MockError::OAuth {
+            status: Status::Unauthorized,
+            error: "invalid_client",
+            description: "client credentials must be sent with basic auth".to_owned(),
+        });
+    }
+
+    let response =
+        match (form.grant_type, form.code, form.refresh_token) {
+            ("authorization_code", Some(code), _) => tokens
+                .exchange_code(code)
+                .ok_or_else(|| invalid_grant("unknown or already used authorization code"))?,
+            ("refresh_token", _, Some(refresh_token)) => tokens
+                .exchange_refresh_token(refresh_token)
+                .ok_or_else(|| invalid_grant("unknown or revoked refresh token"))?,
+            (grant_type, ..) => {
+                return Err(MockError::OAuth {
+                    status: Status::BadRequest,
+                    error: "invalid_request",
+                    description: format!("unsupported or incomplete grant {grant_type}"),
+                });
+            }
+        };
+
+    Ok(Json(response))
+}
+
+#[derive(FromForm)]
+struct RevokeRequest<'r> {
+    token: &'r str,
+}
+
+#[post("/revoke", data = "<form>")]
+fn revoke(
+    faults: &State<Faults>,
+    tokens: &State<Tokens>,
+    form: Form<RevokeRequest<'_>>,
+) -> Result<(), MockError> {
+    faults.check(Scope::OAuth)?;
+    tokens.revoke(form.token);
+    Ok(())
+}
+
+/// Whether the request authenticated the client with HTTP basic auth. Any
+/// client id and secret are accepted.
+struct ClientCredentials(bool);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for ClientCredentials {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let basic = request
+            .headers()
+            .get_one("Authorization")
+            .is_some_and(|header| header.starts_with("Basic "));
+
+        Outcome::Success(Self(basic))
+    }
+}
+
+pub fn routes() -> Vec<Route> {
+    routes![authorize, token, revoke]
+}
@@ -0,0 +1,168 @@
+use rocket::{
+    Route, State,
+    serde::json::{Json, Value, json},
+};
+
+use crate::{
+    MockConfig, MockError,
+    faults::{Faults, Scope},
+    oauth::{Bearer, Tokens},
+};
+
+#[get("/userPreference")]
+fn user_preference(
+    config: &State<MockConfig>,
+    faults: &State<Faults>,
+    tokens: &State<Tokens>,
+    bearer: Bearer,
+) -> Result<Json<Value>, MockError> {
+    faults.check(Scope::Trader)?;
+    tokens.check(&bearer)?;
+
+    let account_number = &config.account_number;
+    let last_digits = &account_number[account_number.len().saturating_sub(3)..];
+
+    Ok(Json(json!({
+        "accounts": [{
+            "accountNumber": account_number,
+            "primaryAccount": true,
+            "type": "BROKERAGE",
+            "nickName": "Individual",
+            "displayAcctId": format!("...{last_digits}"),
+            "autoPositionEffect": false,
+            "accountColor": "Green",
+            "lotSelectionMethod": "FIFO",
+            "hasFuturesAccount": false,
+            "hasForexAccount": false,
+        }],
+        "streamerInfo": [{
+            "streamerSocketUrl": "wss://localhost/ws",
+            "schwabClientCustomerId": "mock-customer",
+            "schwabClientCorrelId": "mock-correl",
+            "schwabClientChannel": "N9",
+            "schwabClientFunctionId": "APIAPP",
+        }],
+        "offers": [{
+            "level2Permissions": false,
+            "mktDataPermission": "NP",
+        }],
+    })))
+}
+
+/// The opaque id trader endpoints use in place of `account_number`.
+fn account_hash(account_number: &str) -> String {
+    format!("MOCKHASH{account_number}")
+}
+
+#[get("/accounts/accountNumbers")]
+fn account_numbers(
+    config: &State<MockConfig>,
+    faults: &State<Faults>,
+    tokens: &State<Tokens>,
+    bearer: Bearer,
+) -> Result<Json<Value>, MockError> {
+    faults.check(Scope::Trader)?;
+    tokens.check(&bearer)?;
+
+    Ok(Json(json!([{
+        "accountNumber": config.account_number,
+        "hashValue": account_hash(&config.account_number),
+    }])))
+}
+
+/// The entered-time window Schwab requires on order listings.
+#[derive(FromForm)]
+struct OrderWindow<'r> {
+    #[field(name = "fromEnteredTime")]
+    from: Option<&'r str>,
+    #[field(name = "toEnteredTime")]
+    to: Option<&'r str>,
+}
+
+/// A fixed order history: a filled market buy, a working limit sell and a
+/// two-leg option spread, which the backend can't represent.
+#[get("/accounts/<hash>/orders?<window..>")]
+fn orders(
+    config: &State<MockConfig>,
+    faults: &State<Faults>,
+    tokens: &State<Tokens>,
+    bearer: Bearer,
+    hash: &str,
+    window: OrderWindow<'_>,
+) -> Result<Json<Value>, MockError> {
+    faults.check(Scope::Trader)?;
+    tokens.check(&bearer)?;
+
+    if hash != account_hash(&config.account_number) {
+        return Err(MockError::BadRequest {
+            detail: "Invalid account number".to_owned(),
+            pointers: vec![],
+        });
+    }
+
+    if window.from.is_none() || window.to.is_none() {
+        return Err(MockError::BadRequest {
+            detail: "fromEnteredTime and toEnteredTime are required".to_owned(),
+            pointers: vec![
+                "/data/attributes/fromEnteredTime".to_owned(),
+                "/data/attributes/toEnteredTime".to_owned(),
+            ],
+        });
+    }
+
+    let leg = |instruction: &str, quantity: f64, symbol: &str, asset_type: &str| {
+        json!({
+            "instruction": instruction,
+            "quantity": quantity,
+            "instrument": { "symbol": symbol, "assetType": asset_type },
+        })
+    };
+
+    Ok(Json(json!([
+        {
+            "orderId": 1001,
+            "orderType": "MARKET",
+            "quantity": 10.0,
+            "filledQuantity": 10.0,
+            "status": "FILLED",
+            "enteredTime": "2025-01-02T15:04:05+0000",
+            "session": "NORMAL",
+            "duration": "DAY",
+            "orderStrategyType": "SINGLE",
+            "orderLegCollection": [leg("BUY", 10.0, "AAPL", "EQUITY")],
+        },
+        {
+            "orderId": 1002,
+            "orderType": "LIMIT",
+            "price": 512.25,
+            "quantity": 5.0,
+            "filledQuantity": 0.0,
+            "status": "WORKING",
+            "enteredTime": "2025-01-03T14:30:00+0000",
+            "session": "NORMAL",
+            "duration": "GOOD_TILL_CANCEL",
+            "orderStrategyType": "SINGLE",
+            "orderLegCollection": [leg("SELL", 5.0, "MSFT", "EQUITY")],
+        },
+        {
+            "orderId": 1003,
+            "orderType": "NET_DEBIT",
+            "price": 1.05,
+            "quantity": 1.0,
+            "filledQuantity": 0.0,
+            "status": "WORKING",
+            "enteredTime": "2025-01-03T14:31:00+0000",
+            "session": "NORMAL",
+            "duration": "DAY",
+            "orderStrategyType": "SINGLE",
+            "orderLegCollection": [
+                leg("BUY_TO_OPEN", 1.0, "AAPL  250117C00200000", "OPTION"),
+                leg("SELL_TO_OPEN", 1.0, "AAPL  250117C00210000", "OPTION"),
+            ],
+        },
+    ])))
+}
+
+pub fn routes() -> Vec<Route> {
+    routes![user_preference, account_numbers, orders]
+}